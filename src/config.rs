@@ -0,0 +1,50 @@
+use serde::Deserialize;
+
+/// Application settings, read from Rocket's configuration so they can be set
+/// in `Rocket.toml` or with `ROCKET_` prefixed environment variables.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The WFS endpoint, e.g. a local stand-in or a caching mirror
+    pub fmi_base_url: String,
+    /// Sent with every FMI request when set, for mirrors that require a key
+    pub fmi_api_key: Option<String>,
+    /// Header the API key is sent in. Without it the key is sent as the
+    /// `fmi_api_key_parameter` query parameter.
+    pub fmi_api_key_header: Option<String>,
+    pub fmi_api_key_parameter: String,
+    /// Whether to fill in station coordinates and names from FMI's station
+    /// catalogue on startup
+    pub load_station_catalogue: bool,
+    /// Lightning strikes within this distance of a station are counted
+    pub lightning_radius_km: f64,
+    /// Time allowed for connecting to FMI
+    pub fmi_connect_timeout_seconds: u64,
+    /// Time allowed between reads of an FMI response
+    pub fmi_read_timeout_seconds: u64,
+    /// Retries of a failed FMI request, not counting the first attempt
+    pub fmi_max_retries: u32,
+    /// Observations older than this are flagged as stale
+    pub stale_after_minutes: i64,
+    /// How far back to look for the last observation of a station that has
+    /// nothing within the last hour
+    pub stale_lookback_hours: i64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            fmi_base_url: "https://opendata.fmi.fi/wfs".to_string(),
+            fmi_api_key: None,
+            fmi_api_key_header: None,
+            fmi_api_key_parameter: "apikey".to_string(),
+            load_station_catalogue: true,
+            lightning_radius_km: 20.0,
+            fmi_connect_timeout_seconds: 5,
+            fmi_read_timeout_seconds: 15,
+            fmi_max_retries: 2,
+            stale_after_minutes: 30,
+            stale_lookback_hours: 24,
+        }
+    }
+}
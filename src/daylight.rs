@@ -0,0 +1,172 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
+use chrono_tz::Europe::Helsinki;
+use serde::Serialize;
+
+/// Zenith angles of the events, in degrees. Sunrise and sunset allow for
+/// refraction and the radius of the sun.
+const SUNRISE_ZENITH: f64 = 90.833;
+const CIVIL_ZENITH: f64 = 96.0;
+const NAUTICAL_ZENITH: f64 = 102.0;
+
+/// Sunrise, sunset and twilight of one day in Helsinki time. An event is
+/// missing when the sun does not reach its angle that day, e.g. nautical
+/// twilight lasts all night around midsummer.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Daylight {
+    pub date: NaiveDate,
+    pub nautical_dawn: Option<DateTime<FixedOffset>>,
+    pub civil_dawn: Option<DateTime<FixedOffset>>,
+    pub sunrise: Option<DateTime<FixedOffset>>,
+    pub sunset: Option<DateTime<FixedOffset>>,
+    pub civil_dusk: Option<DateTime<FixedOffset>>,
+    pub nautical_dusk: Option<DateTime<FixedOffset>>,
+}
+
+/// Computes the daylight of the Helsinki date of `now` at a location, with
+/// NOAA's solar position equations.
+pub fn compute_daylight(latitude: f64, longitude: f64, now: DateTime<Utc>) -> Daylight {
+    let date = now.with_timezone(&Helsinki).date_naive();
+    let event = |zenith, rising| {
+        solar_event(date, latitude, longitude, zenith, rising)
+            .map(|time| time.with_timezone(&Helsinki).fixed_offset())
+    };
+    Daylight {
+        date,
+        nautical_dawn: event(NAUTICAL_ZENITH, true),
+        civil_dawn: event(CIVIL_ZENITH, true),
+        sunrise: event(SUNRISE_ZENITH, true),
+        sunset: event(SUNRISE_ZENITH, false),
+        civil_dusk: event(CIVIL_ZENITH, false),
+        nautical_dusk: event(NAUTICAL_ZENITH, false),
+    }
+}
+
+/// The time the sun passes `zenith` degrees on `date`, rising or setting.
+/// Starts from solar noon and refines the time with the sun's position at
+/// the previous estimate.
+fn solar_event(
+    date: NaiveDate,
+    latitude: f64,
+    longitude: f64,
+    zenith: f64,
+    rising: bool,
+) -> Option<DateTime<Utc>> {
+    let midnight = date.and_time(Default::default()).and_utc();
+    let mut time = midnight + Duration::minutes((720.0 - 4.0 * longitude) as i64);
+    for _ in 0..3 {
+        let (declination, equation_of_time) = solar_position(time);
+        let hour_angle = hour_angle(latitude, declination, zenith)?;
+        let solar_noon = 720.0 - 4.0 * longitude - equation_of_time;
+        let minutes = if rising {
+            solar_noon - 4.0 * hour_angle
+        } else {
+            solar_noon + 4.0 * hour_angle
+        };
+        time = midnight + Duration::seconds((minutes * 60.0).round() as i64);
+    }
+    Some(time)
+}
+
+/// The hour angle in degrees at which the sun is at `zenith`, or `None` when
+/// it stays above or below it all day.
+fn hour_angle(latitude: f64, declination: f64, zenith: f64) -> Option<f64> {
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = zenith.to_radians().cos() / (latitude.cos() * declination.cos())
+        - latitude.tan() * declination.tan();
+    (-1.0..=1.0)
+        .contains(&cos_hour_angle)
+        .then(|| cos_hour_angle.acos().to_degrees())
+}
+
+/// The sun's declination in radians and the equation of time in minutes.
+fn solar_position(time: DateTime<Utc>) -> (f64, f64) {
+    let julian_day = time.timestamp() as f64 / 86400.0 + 2440587.5;
+    let t = (julian_day - 2451545.0) / 36525.0;
+
+    let mean_longitude = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0);
+    let mean_anomaly = 357.52911 + t * (35999.05029 - 0.0001537 * t);
+    let eccentricity = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+    let m = mean_anomaly.to_radians();
+    let center = m.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + (2.0 * m).sin() * (0.019993 - 0.000101 * t)
+        + (3.0 * m).sin() * 0.000289;
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let apparent_longitude = mean_longitude + center - 0.00569 - 0.00478 * omega.sin();
+    let mean_obliquity =
+        23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+
+    let declination = (obliquity.sin() * apparent_longitude.to_radians().sin()).asin();
+
+    let y = (obliquity / 2.0).tan().powi(2);
+    let l = mean_longitude.to_radians();
+    let equation_of_time = 4.0
+        * (y * (2.0 * l).sin() - 2.0 * eccentricity * m.sin()
+            + 4.0 * eccentricity * y * m.sin() * (2.0 * l).cos()
+            - 0.5 * y * y * (4.0 * l).sin()
+            - 1.25 * eccentricity * eccentricity * (2.0 * m).sin())
+        .to_degrees();
+
+    (declination, equation_of_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveTime, TimeZone};
+
+    const HELSINKI: (f64, f64) = (60.17, 24.94);
+
+    fn daylight_on(year: i32, month: u32, day: u32) -> Daylight {
+        let midday = Utc.with_ymd_and_hms(year, month, day, 10, 0, 0).unwrap();
+        compute_daylight(HELSINKI.0, HELSINKI.1, midday)
+    }
+
+    /// Asserts that an event is within two minutes of `hour:minute`
+    /// Helsinki time.
+    fn assert_near(event: Option<DateTime<FixedOffset>>, hour: u32, minute: u32) {
+        let event = event.expect("event should happen").time();
+        let expected = NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+        let difference = (event - expected).num_seconds().abs();
+        assert!(difference <= 120, "{} is not near {}", event, expected);
+    }
+
+    #[test]
+    fn midsummer_has_no_nautical_twilight() {
+        let daylight = daylight_on(2026, 6, 21);
+        assert_near(daylight.sunrise, 3, 54);
+        assert_near(daylight.sunset, 22, 50);
+        assert!(daylight.nautical_dawn.is_none());
+        assert!(daylight.nautical_dusk.is_none());
+    }
+
+    #[test]
+    fn midwinter_day_is_short() {
+        let daylight = daylight_on(2026, 12, 21);
+        assert_near(daylight.sunrise, 9, 24);
+        assert_near(daylight.sunset, 15, 13);
+        assert_eq!(daylight.sunrise.unwrap().offset().local_minus_utc(), 7200);
+    }
+
+    #[test]
+    fn twilight_surrounds_the_day_in_order() {
+        let daylight = daylight_on(2026, 10, 18);
+        assert_near(daylight.sunrise, 8, 7);
+        assert_near(daylight.sunset, 18, 2);
+        let events = [
+            daylight.nautical_dawn,
+            daylight.civil_dawn,
+            daylight.sunrise,
+            daylight.sunset,
+            daylight.civil_dusk,
+            daylight.nautical_dusk,
+        ];
+        let events: Vec<_> = events.iter().map(|event| event.unwrap()).collect();
+        assert!(events.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            daylight.date,
+            NaiveDate::from_ymd_opt(2026, 10, 18).unwrap()
+        );
+    }
+}
@@ -1,164 +1,687 @@
-use chrono::{DateTime, Utc};
-use chrono_tz::Europe::Helsinki;
+use chrono::{DateTime, Duration, Utc};
 use quick_xml::{events::Event, Reader};
-use reqwest_middleware::{ClientWithMiddleware, Result as ReqwestResult};
-use rocket::State;
-use serde::Serialize;
+use reqwest::StatusCode;
+use reqwest_middleware::ClientWithMiddleware;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
 
+use crate::model::{
+    Observation, ObservationSeries, ParameterType, Reading, TimeRange, WeatherData,
+};
+use crate::provider::ProviderError;
+use crate::stations::{create_batch_weather_url, WeatherLocation};
 use crate::utils::map_degrees_to_compass;
 
+pub mod cache;
+pub mod catalogue;
+pub mod client;
+pub mod forecast;
+pub mod history;
+pub mod lightning;
+pub mod mareograph;
+mod multipointcoverage;
+pub mod provider;
+mod simple;
+pub mod wave;
+
+#[derive(Debug, Clone)]
+pub enum FmiError {
+    /// The request to FMI could not be completed. Shared, so that everyone
+    /// waiting on the same fetch gets the error.
+    Network(Arc<reqwest_middleware::Error>),
+    /// FMI responded with a non-success status code.
+    HttpStatus(StatusCode),
+    /// The response body was not valid XML.
+    MalformedXml(String),
+    /// FMI rejected the query with an `ows:ExceptionReport`.
+    ExceptionReport { code: String, text: String },
+    /// The response did not contain any observations.
+    NoData,
+}
+
+impl fmt::Display for FmiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FmiError::Network(e) => write!(f, "request to FMI failed: {}", e),
+            FmiError::HttpStatus(status) => write!(f, "FMI responded with status {}", status),
+            FmiError::MalformedXml(message) => write!(f, "malformed XML: {}", message),
+            FmiError::ExceptionReport { code, text } => {
+                write!(f, "FMI exception report {}: {}", code, text)
+            }
+            FmiError::NoData => write!(f, "no observations in response"),
+        }
+    }
+}
+
+impl std::error::Error for FmiError {}
+
+impl FmiError {
+    /// Whether FMI did not respond in time. The retry layer wraps the error
+    /// of the last attempt, so the whole chain of sources is checked.
+    pub fn is_timeout(&self) -> bool {
+        let FmiError::Network(e) = self else {
+            return false;
+        };
+        let mut source: Option<&(dyn std::error::Error + 'static)> = Some(e.as_ref());
+        while let Some(error) = source {
+            let timed_out = match (
+                error.downcast_ref::<reqwest_middleware::Error>(),
+                error.downcast_ref::<reqwest::Error>(),
+            ) {
+                (Some(e), _) => e.is_timeout(),
+                (_, Some(e)) => e.is_timeout(),
+                _ => false,
+            };
+            if timed_out {
+                return true;
+            }
+            source = error.source();
+        }
+        false
+    }
+}
+
+impl From<FmiError> for ProviderError {
+    fn from(e: FmiError) -> Self {
+        if e.is_timeout() {
+            return ProviderError::Timeout;
+        }
+        match e {
+            FmiError::Network(e) => ProviderError::Network(e.to_string()),
+            FmiError::HttpStatus(status) => ProviderError::HttpStatus(status.as_u16()),
+            FmiError::MalformedXml(message) => ProviderError::InvalidResponse(message),
+            FmiError::ExceptionReport { code, text } => ProviderError::Rejected { code, text },
+            FmiError::NoData => ProviderError::NoData,
+        }
+    }
+}
+
+impl From<reqwest_middleware::Error> for FmiError {
+    fn from(e: reqwest_middleware::Error) -> Self {
+        FmiError::Network(Arc::new(e))
+    }
+}
+
+impl From<reqwest::Error> for FmiError {
+    fn from(e: reqwest::Error) -> Self {
+        FmiError::Network(Arc::new(e.into()))
+    }
+}
+
+/// Fetches the observations of `time_range`, one response per chunk of at
+/// most `MAX_QUERY_RANGE_HOURS`, ordered by time.
 pub async fn fetch_xml(
     (weather_station_location, url): (&str, &str),
-    reqwest_client: &State<ClientWithMiddleware>,
-) -> ReqwestResult<String> {
+    time_range: TimeRange,
+    reqwest_client: &ClientWithMiddleware,
+) -> Result<Vec<String>, FmiError> {
     println!("Fetching weather data for {}", weather_station_location);
-    let utc: DateTime<Utc> = Utc::now();
-    let utc_minus_1_hour = utc - chrono::Duration::hours(1);
-    // Format as 2021-05-01T12:00:00Z
-    let formatted_time = utc_minus_1_hour.format("%Y-%m-%dT%H:%M:%SZ");
-    let url = format!("{}&starttime={}", url, formatted_time);
-    let response = reqwest_client.get(url).send().await?;
-    let body = response.text().await?;
-    Ok(body)
+    let mut documents = Vec::new();
+    for chunk in time_range.chunks() {
+        let url = format!("{}{}", url, chunk.query_parameters());
+        documents.push(get_xml(&url, reqwest_client).await?);
+    }
+    Ok(documents)
+}
+
+/// Longest time range FMI accepts in one observation query.
+const MAX_QUERY_RANGE_HOURS: i64 = 168;
+
+impl TimeRange {
+    /// Splits the range into consecutive ranges FMI accepts. Consecutive
+    /// chunks share their boundary, so its observations appear twice.
+    fn chunks(self) -> Vec<TimeRange> {
+        let max_length = Duration::hours(MAX_QUERY_RANGE_HOURS);
+        let mut chunks = Vec::new();
+        let mut start = self.start;
+        loop {
+            let end = self.end.min(start + max_length);
+            chunks.push(TimeRange { start, end, ..self });
+            if end >= self.end {
+                break;
+            }
+            start = end;
+        }
+        chunks
+    }
+
+    fn query_parameters(&self) -> String {
+        let mut parameters = format!(
+            "&starttime={}&endtime={}",
+            format_query_time(self.start),
+            format_query_time(self.end)
+        );
+        if let Some(timestep) = self.timestep {
+            parameters.push_str(&format!("&timestep={}", timestep.num_minutes()));
+        }
+        parameters
+    }
 }
 
-enum ParameterType {
-    WindSpeed,
-    Temperature,
-    WindDirection,
-    WindGustSpeed,
+/// Most stations asked from FMI in a single request, to keep the URL and the
+/// response at a reasonable size.
+const MAX_STATIONS_PER_REQUEST: usize = 20;
+
+/// Fetches the latest weather data of many stations with one `getFeature`
+/// call per `MAX_STATIONS_PER_REQUEST` stations and splits the response by
+/// station. Stations without observations are left out of the result, as are
+/// the stations of a chunk that fails.
+pub async fn fetch_latest_weather_data_batch(
+    locations: &HashMap<String, WeatherLocation>,
+    base_url: &str,
+    reqwest_client: &ClientWithMiddleware,
+) -> HashMap<String, WeatherData> {
+    let mut station_ids: Vec<(&String, i32)> = locations
+        .iter()
+        .map(|(id, location)| (id, location.fmis_id))
+        .collect();
+    station_ids.sort();
+
+    let mut weather_data = HashMap::new();
+    for chunk in station_ids.chunks(MAX_STATIONS_PER_REQUEST) {
+        let fmis_ids: Vec<i32> = chunk.iter().map(|(_, fmis_id)| *fmis_id).collect();
+        println!("Fetching weather data for {} stations", fmis_ids.len());
+        let by_fmis_id = match get_latest_observations_xml(
+            &create_batch_weather_url(base_url, &fmis_ids),
+            reqwest_client,
+        )
+        .await
+        .and_then(|xml| parse_latest_weather_data_by_station(&xml))
+        {
+            Ok(by_fmis_id) => by_fmis_id,
+            // None of the stations of the chunk has reported
+            Err(FmiError::NoData) => continue,
+            Err(e) => {
+                println!("Failed to fetch weather data for {:?}: {}", fmis_ids, e);
+                continue;
+            }
+        };
+        weather_data.extend(split_by_station(chunk, by_fmis_id));
+    }
+    weather_data
 }
 
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct WeatherData {
-    pub time_of_observation: String,
-    pub wind_speed_meters_per_second: String,
-    pub temperature_celsius: String,
-    pub wind_direction: String,
-    pub wind_gust_speed_meters_per_second: String,
+/// Keys the weather data of a batch by station id instead of fmisid.
+fn split_by_station(
+    station_ids: &[(&String, i32)],
+    mut by_fmis_id: HashMap<i32, WeatherData>,
+) -> HashMap<String, WeatherData> {
+    station_ids
+        .iter()
+        .filter_map(|(id, fmis_id)| Some((id.to_string(), by_fmis_id.remove(fmis_id)?)))
+        .collect()
 }
 
-pub fn parse_latest_weather_data(xml: &str) -> Option<WeatherData> {
+/// Fetches the observations of the last hour.
+async fn get_latest_observations_xml(
+    url: &str,
+    reqwest_client: &ClientWithMiddleware,
+) -> Result<String, FmiError> {
+    let url = format!("{}{}", url, TimeRange::latest().query_parameters());
+    get_xml(&url, reqwest_client).await
+}
+
+/// Formats a time for FMI query parameters, e.g. 2021-05-01T12:00:00Z
+fn format_query_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+async fn get_xml(url: &str, reqwest_client: &ClientWithMiddleware) -> Result<String, FmiError> {
+    let response = reqwest_client.get(url).send().await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        // Rejected queries come back as an exception report with status 400
+        return Err(parse_exception_report(&body).unwrap_or(FmiError::HttpStatus(status)));
+    }
+    Ok(body)
+}
+
+/// Parses an `ows:ExceptionReport` document into `FmiError::ExceptionReport`.
+/// Returns `None` if the document is not an exception report.
+pub fn parse_exception_report(xml: &str) -> Option<FmiError> {
     let mut reader = Reader::from_str(xml);
     reader.trim_text(true);
 
     let mut buf = Vec::new();
-    let mut time_of_observation: Option<String> = None;
-    let mut current_wind_speed: Option<String> = None;
-    let mut current_temperature: Option<String> = None;
-    let mut current_wind_direction: Option<String> = None;
-    let mut current_wind_gust_speed: Option<String> = None;
-    let mut latest_data: Option<WeatherData> = None;
-    let mut current_parameter: Option<ParameterType> = None;
+    let mut is_exception_report = false;
+    let mut code: Option<String> = None;
+    let mut texts: Vec<String> = Vec::new();
 
     loop {
         match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(e)) => match e.name().as_ref() {
-                b"BsWfs:ParameterName" => match reader.read_text(e.name()) {
-                    Ok(contents) => {
-                        current_parameter = match contents.to_string().as_str() {
-                            "WindSpeedMS" => Some(ParameterType::WindSpeed),
-                            "Temperature" => Some(ParameterType::Temperature),
-                            "WindDirection" => Some(ParameterType::WindDirection),
-                            "WindGust" => Some(ParameterType::WindGustSpeed),
-                            _ => None,
-                        };
-                    }
-                    Err(e) => {
-                        println!("Failed to read text: {}", e);
-                        continue;
-                    }
-                },
-                b"BsWfs:ParameterValue" => match reader.read_text(e.name()) {
-                    Ok(contents) => {
-                        let contents = contents.to_string();
-                        match current_parameter {
-                            Some(ParameterType::WindSpeed) => {
-                                current_wind_speed = Some(contents);
-                            }
-                            Some(ParameterType::Temperature) => {
-                                current_temperature = Some(contents);
-                            }
-                            Some(ParameterType::WindDirection) => {
-                                let degrees: f32 = match contents.parse() {
-                                    Ok(degrees) => degrees,
-                                    Err(_) => {
-                                        println!("Failed to parse wind direction: {}", contents);
-                                        continue;
-                                    }
-                                };
-                                let compass_direction =
-                                    match map_degrees_to_compass::convert(degrees) {
-                                        Some(direction) => direction.to_string(),
-                                        None => "Unknown".to_string(),
-                                    };
-                                current_wind_direction = Some(compass_direction);
-                            }
-                            Some(ParameterType::WindGustSpeed) => {
-                                current_wind_gust_speed = Some(contents);
-                            }
-                            _ => (),
-                        }
-                    }
-                    Err(e) => {
-                        println!("Failed to read text: {}", e);
-                        continue;
-                    }
-                },
-                b"BsWfs:Time" => {
-                    let contents = reader.read_text(e.name());
-                    match contents {
-                        Ok(text) => match text.parse::<DateTime<Utc>>() {
-                            Ok(dt) => {
-                                let dt_helsinki = dt
-                                    .with_timezone(&Helsinki)
-                                    .format("%d/%m/%Y %T")
-                                    .to_string();
-                                time_of_observation = Some(dt_helsinki);
-                            }
-                            Err(err) => {
-                                println!("Failed to parse time: {}", err);
-                                continue;
-                            }
-                        },
-                        Err(e) => {
-                            // Handle the error from reading the text
-                            println!("Failed to read text: {}", e);
-                            continue;
+            Ok(Event::Start(e)) => match e.local_name().as_ref() {
+                b"ExceptionReport" => is_exception_report = true,
+                b"Exception" if code.is_none() => {
+                    code = e
+                        .try_get_attribute("exceptionCode")
+                        .ok()
+                        .flatten()
+                        .and_then(|attribute| attribute.unescape_value().ok())
+                        .map(|value| value.to_string());
+                }
+                b"ExceptionText" => {
+                    if let Ok(text) = reader.read_text(e.name()) {
+                        // FMI echoes the request URI as an extra exception text
+                        if !text.starts_with("URI:") {
+                            texts.push(text.trim().to_string());
                         }
                     }
                 }
+                _ if !is_exception_report => return None,
                 _ => (),
             },
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-            Ok(Event::Eof) => break,
+            Ok(Event::Eof) | Err(_) => break,
             _ => (),
         }
         buf.clear();
     }
 
-    if let (
-        Some(time_of_observation),
-        Some(wind_speed),
-        Some(temperature),
-        Some(wind_direction),
-        Some(wind_gust_speed),
-    ) = (
-        time_of_observation,
-        current_wind_speed,
-        current_temperature,
-        current_wind_direction,
-        current_wind_gust_speed,
-    ) {
-        latest_data = Some(WeatherData {
-            time_of_observation,
-            wind_speed_meters_per_second: wind_speed,
-            temperature_celsius: temperature,
-            wind_direction,
-            wind_gust_speed_meters_per_second: wind_gust_speed,
+    if !is_exception_report {
+        return None;
+    }
+    Some(FmiError::ExceptionReport {
+        code: code.unwrap_or_else(|| "Unknown".to_string()),
+        text: texts.join(" "),
+    })
+}
+
+impl ParameterType {
+    pub fn from_fmi_name(name: &str) -> Option<ParameterType> {
+        match name {
+            "WindSpeedMS" => Some(ParameterType::WindSpeed),
+            "Temperature" => Some(ParameterType::Temperature),
+            "WindDirection" => Some(ParameterType::WindDirection),
+            "WindGust" => Some(ParameterType::WindGustSpeed),
+            "Pressure" => Some(ParameterType::Pressure),
+            "Humidity" => Some(ParameterType::Humidity),
+            "DewPoint" => Some(ParameterType::DewPoint),
+            "Visibility" => Some(ParameterType::Visibility),
+            "TotalCloudCover" => Some(ParameterType::TotalCloudCover),
+            "Precipitation1h" => Some(ParameterType::Precipitation1h),
+            "WaveHs" | "SigWaveHeight" => Some(ParameterType::WaveHeight),
+            "WTP" | "WavePeriod" => Some(ParameterType::WavePeriod),
+            "ModalWDi" | "WaveDirection" => Some(ParameterType::WaveDirection),
+            "TWATER" => Some(ParameterType::WaterTemperature),
+            "WLEVN2K_PT1S_AVG" => Some(ParameterType::SeaLevelN2000),
+            "WLEV_PT1S_AVG" => Some(ParameterType::SeaLevelTheoreticalMean),
+            "SeaLevel" => Some(ParameterType::SeaLevelForecast),
+            "SeaLevelN2000" => Some(ParameterType::SeaLevelForecastN2000),
+            "peak_current" => Some(ParameterType::LightningPeakCurrent),
+            "tmin" => Some(ParameterType::DailyMinTemperature),
+            "tmax" => Some(ParameterType::DailyMaxTemperature),
+            "WS_PT1H_AVG" => Some(ParameterType::HourlyMeanWindSpeed),
+            "WG_PT1H_MAX" => Some(ParameterType::HourlyMaxGustSpeed),
+            _ => None,
+        }
+    }
+}
+
+/// A single parameter value as it appears in a response, before any
+/// grouping.
+struct RawObservation {
+    /// Only known for `MultiPointCoverage` responses
+    fmis_id: Option<i32>,
+    /// Latitude and longitude of the observation
+    coordinates: Option<(f64, f64)>,
+    time: DateTime<Utc>,
+    parameter: ParameterType,
+    value: String,
+}
+
+fn malformed_xml(position: usize, e: quick_xml::Error) -> FmiError {
+    FmiError::MalformedXml(format!("error at position {}: {}", position, e))
+}
+
+/// The stored query response formats we know how to parse. `Simple` repeats
+/// the whole element wrapper for every value, `MultiPointCoverage` sends
+/// positions and values as compact whitespace separated blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Simple,
+    MultiPointCoverage,
+}
+
+impl ResponseFormat {
+    /// The last part of the stored query id, e.g. `simple` in
+    /// `fmi::observations::weather::simple`.
+    pub fn stored_query_suffix(self) -> &'static str {
+        match self {
+            ResponseFormat::Simple => "simple",
+            ResponseFormat::MultiPointCoverage => "multipointcoverage",
+        }
+    }
+
+    fn parse_raw_observations(self, xml: &str) -> Result<Vec<RawObservation>, FmiError> {
+        let observations = match self {
+            ResponseFormat::Simple => simple::parse_raw_observations(xml)?,
+            ResponseFormat::MultiPointCoverage => multipointcoverage::parse_raw_observations(xml)?,
+        };
+        if observations.is_empty() {
+            return Err(FmiError::NoData);
+        }
+        Ok(observations)
+    }
+
+    /// Parses the responses of a chunked query together. Chunks without
+    /// observations are skipped as long as one of them has some.
+    fn parse_documents(self, documents: &[String]) -> Result<Vec<RawObservation>, FmiError> {
+        let mut observations = Vec::new();
+        for xml in documents {
+            match self.parse_raw_observations(xml) {
+                Ok(chunk) => observations.extend(chunk),
+                Err(FmiError::NoData) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        if observations.is_empty() {
+            return Err(FmiError::NoData);
+        }
+        Ok(observations)
+    }
+}
+
+/// Parses every observation of the responses of `fetch_xml` instead of only
+/// the latest ones.
+pub fn parse_observation_series(
+    documents: &[String],
+    format: ResponseFormat,
+) -> Result<ObservationSeries, FmiError> {
+    let mut series = ObservationSeries::default();
+    for element in format.parse_documents(documents)? {
+        let value = element.value.parse::<f64>().ok().filter(|v| !v.is_nan());
+        series
+            .parameters
+            .entry(element.parameter)
+            .or_default()
+            .push(Observation {
+                time: element.time,
+                value,
+            });
+    }
+    for observations in series.parameters.values_mut() {
+        observations.sort_by_key(|observation| observation.time);
+        // Chunk boundaries are in two responses
+        observations.dedup_by_key(|observation| observation.time);
+    }
+    Ok(series)
+}
+
+pub fn parse_latest_weather_data(
+    documents: &[String],
+    format: ResponseFormat,
+) -> Result<WeatherData, FmiError> {
+    latest_weather_data(format.parse_documents(documents)?)
+}
+
+/// Parses `lat lon` coordinates as they appear in `gml:pos` and
+/// `gmlcov:positions`.
+fn parse_coordinates(text: &str) -> Option<(f64, f64)> {
+    let mut parts = text.split_whitespace().map(|part| part.parse::<f64>().ok());
+    Some((parts.next()??, parts.next()??))
+}
+
+/// Parses a `multipointcoverage` response of several stations into the
+/// latest weather data of each station, keyed by fmisid.
+pub fn parse_latest_weather_data_by_station(
+    xml: &str,
+) -> Result<HashMap<i32, WeatherData>, FmiError> {
+    let mut by_station: HashMap<i32, Vec<RawObservation>> = HashMap::new();
+    for observation in ResponseFormat::MultiPointCoverage.parse_raw_observations(xml)? {
+        if let Some(fmis_id) = observation.fmis_id {
+            by_station.entry(fmis_id).or_default().push(observation);
+        }
+    }
+    Ok(by_station
+        .into_iter()
+        .filter_map(|(fmis_id, observations)| {
+            latest_weather_data(observations)
+                .ok()
+                .map(|data| (fmis_id, data))
+        })
+        .collect())
+}
+
+/// Parameter values grouped by timestamp, leaving out missing values.
+struct Records(BTreeMap<DateTime<Utc>, HashMap<ParameterType, f64>>);
+
+impl Records {
+    fn from_raw(observations: Vec<RawObservation>) -> Records {
+        let mut records: BTreeMap<DateTime<Utc>, HashMap<ParameterType, f64>> = BTreeMap::new();
+        for element in observations {
+            let value = match element.value.parse::<f64>() {
+                Ok(value) if !value.is_nan() => value,
+                Ok(_) => continue,
+                Err(_) => {
+                    println!("Failed to parse value: {}", element.value);
+                    continue;
+                }
+            };
+            records
+                .entry(element.time)
+                .or_default()
+                .insert(element.parameter, value);
+        }
+        Records(records)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&DateTime<Utc>, &HashMap<ParameterType, f64>)> {
+        self.0.iter()
+    }
+
+    fn newest_time(&self) -> Result<DateTime<Utc>, FmiError> {
+        self.0.keys().next_back().copied().ok_or(FmiError::NoData)
+    }
+
+    /// The newest value of `parameter`, which may be older than the newest
+    /// record.
+    fn latest_reading(&self, parameter: ParameterType) -> Option<Reading<f64>> {
+        self.0.iter().rev().find_map(|(time, record)| {
+            record.get(&parameter).map(|value| Reading {
+                value: *value,
+                time_of_observation: *time,
+            })
+        })
+    }
+
+    /// Whether the newest record has every parameter the station reports.
+    fn is_complete(&self) -> bool {
+        let newest_record = match self.0.values().next_back() {
+            Some(record) => record,
+            None => return false,
+        };
+        self.0
+            .values()
+            .flat_map(|record| record.keys())
+            .all(|parameter| newest_record.contains_key(parameter))
+    }
+
+    /// The newest value of a direction parameter in degrees together with
+    /// its compass point.
+    fn latest_direction(
+        &self,
+        parameter: ParameterType,
+    ) -> (Option<Reading<f32>>, Option<&'static str>) {
+        let degrees = self.latest_reading(parameter).map(|reading| Reading {
+            value: reading.value as f32,
+            time_of_observation: reading.time_of_observation,
         });
+        let compass = degrees.and_then(|reading| map_degrees_to_compass::convert(reading.value));
+        (degrees, compass)
+    }
+}
+
+fn latest_weather_data(observations: Vec<RawObservation>) -> Result<WeatherData, FmiError> {
+    let records = Records::from_raw(observations);
+    let (wind_direction_degrees, wind_direction_compass) =
+        records.latest_direction(ParameterType::WindDirection);
+
+    Ok(WeatherData {
+        time_of_observation: records.newest_time()?,
+        complete: records.is_complete(),
+        wind_speed_meters_per_second: records.latest_reading(ParameterType::WindSpeed),
+        temperature_celsius: records.latest_reading(ParameterType::Temperature),
+        wind_direction_degrees,
+        wind_direction_compass,
+        wind_gust_speed_meters_per_second: records.latest_reading(ParameterType::WindGustSpeed),
+        pressure_hectopascals: records.latest_reading(ParameterType::Pressure),
+        humidity_percent: records.latest_reading(ParameterType::Humidity),
+        dew_point_celsius: records.latest_reading(ParameterType::DewPoint),
+        visibility_meters: records.latest_reading(ParameterType::Visibility),
+        total_cloud_cover_oktas: records.latest_reading(ParameterType::TotalCloudCover),
+        precipitation_one_hour_millimeters: records.latest_reading(ParameterType::Precipitation1h),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_exception_report_without_the_echoed_uri() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ExceptionReport xmlns="http://www.opengis.net/ows/1.1" version="2.0.0">
+  <Exception exceptionCode="OperationParsingFailed" locator="fmisid">
+    <ExceptionText>Invalid parameter value for 'fmisid'.</ExceptionText>
+    <ExceptionText>URI: /wfs?fmisid=1&amp;request=getFeature</ExceptionText>
+  </Exception>
+</ExceptionReport>"#;
+        match parse_exception_report(xml) {
+            Some(FmiError::ExceptionReport { code, text }) => {
+                assert_eq!(code, "OperationParsingFailed");
+                assert_eq!(text, "Invalid parameter value for 'fmisid'.");
+            }
+            other => panic!("expected an exception report, got {:?}", other),
+        }
     }
 
-    latest_data
+    #[test]
+    fn exception_report_without_code_is_unknown() {
+        let xml = r#"<ows:ExceptionReport xmlns:ows="http://www.opengis.net/ows/1.1">
+  <ows:Exception><ows:ExceptionText>Too many requests</ows:ExceptionText></ows:Exception>
+</ows:ExceptionReport>"#;
+        match parse_exception_report(xml) {
+            Some(FmiError::ExceptionReport { code, text }) => {
+                assert_eq!(code, "Unknown");
+                assert_eq!(text, "Too many requests");
+            }
+            other => panic!("expected an exception report, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn feature_collection_is_not_an_exception_report() {
+        let xml = r#"<wfs:FeatureCollection xmlns:wfs="http://www.opengis.net/wfs/2.0" numberMatched="0" numberReturned="0"/>"#;
+        assert!(parse_exception_report(xml).is_none());
+        let xml = r#"<wfs:FeatureCollection xmlns:wfs="http://www.opengis.net/wfs/2.0"><wfs:member/></wfs:FeatureCollection>"#;
+        assert!(parse_exception_report(xml).is_none());
+    }
+
+    /// A batch response where the last two rows are at a point that is not
+    /// one of the stations.
+    const BATCH: &str = r#"<wfs:FeatureCollection xmlns:wfs="http://www.opengis.net/wfs/2.0" xmlns:gml="http://www.opengis.net/gml/3.2" xmlns:omso="http://inspire.ec.europa.eu/schemas/omso/3.0" xmlns:gmlcov="http://www.opengis.net/gmlcov/1.0" xmlns:swe="http://www.opengis.net/swe/2.0">
+<wfs:member><omso:GridSeriesObservation gml:id="obs-1">
+<gml:Point gml:id="point-100947"><gml:pos>60.37750 22.09630 </gml:pos></gml:Point>
+<gml:Point gml:id="point-100908"><gml:pos>59.77909 21.37479 </gml:pos></gml:Point>
+<gmlcov:positions>
+    60.37750 22.09630  1717242600
+    60.37750 22.09630  1717243200
+    59.77909 21.37479  1717242600
+    59.77909 21.37479  1717243200
+    60.00000 22.00000  1717242600
+    60.00000 22.00000  1717243200
+</gmlcov:positions>
+<gml:doubleOrNilReasonTupleList>
+    14.2 6.1
+    14.5 NaN
+    12.9 9.1
+    13.0 9.5
+    1.0 1.0
+    2.0 2.0
+</gml:doubleOrNilReasonTupleList>
+<swe:field name="Temperature"/>
+<swe:field name="WindSpeedMS"/>
+</omso:GridSeriesObservation></wfs:member>
+</wfs:FeatureCollection>"#;
+
+    #[test]
+    fn splits_batch_by_matching_coordinates() {
+        let by_fmis_id = parse_latest_weather_data_by_station(BATCH).unwrap();
+
+        let mut fmis_ids: Vec<i32> = by_fmis_id.keys().copied().collect();
+        fmis_ids.sort();
+        assert_eq!(fmis_ids, vec![100908, 100947]);
+
+        let rajakari = &by_fmis_id[&100947];
+        assert_eq!(rajakari.time_of_observation.timestamp(), 1717243200);
+        assert_eq!(rajakari.temperature_celsius.unwrap().value, 14.5);
+        // The newest wind speed is missing, so the one before it is used
+        let wind_speed = rajakari.wind_speed_meters_per_second.unwrap();
+        assert_eq!(wind_speed.value, 6.1);
+        assert_eq!(wind_speed.time_of_observation.timestamp(), 1717242600);
+
+        assert_eq!(by_fmis_id[&100908].temperature_celsius.unwrap().value, 13.0);
+    }
+
+    #[test]
+    fn keys_batch_by_station_id() {
+        let by_fmis_id = parse_latest_weather_data_by_station(BATCH).unwrap();
+        let (rajakari, uto, harmaja) = (
+            "rajakari".to_string(),
+            "uto".to_string(),
+            "harmaja".to_string(),
+        );
+        let station_ids = [(&rajakari, 100947), (&uto, 100908), (&harmaja, 100996)];
+
+        let by_station = split_by_station(&station_ids, by_fmis_id);
+
+        assert_eq!(by_station.len(), 2);
+        assert_eq!(by_station["uto"].temperature_celsius.unwrap().value, 13.0);
+        assert!(!by_station.contains_key("harmaja"));
+    }
+
+    fn time_range(hours: i64) -> TimeRange {
+        let start = "2024-06-01T00:00:00Z".parse().unwrap();
+        TimeRange {
+            start,
+            end: start + Duration::hours(hours),
+            timestep: Some(Duration::minutes(60)),
+        }
+    }
+
+    #[test]
+    fn short_range_is_one_chunk() {
+        let range = time_range(MAX_QUERY_RANGE_HOURS);
+        assert_eq!(range.chunks(), vec![range]);
+    }
+
+    #[test]
+    fn long_range_is_split_into_chunks_sharing_their_boundary() {
+        let range = time_range(10 * 24);
+        let chunks = range.chunks();
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].start, range.start);
+        assert_eq!(
+            chunks[0].end,
+            range.start + Duration::hours(MAX_QUERY_RANGE_HOURS)
+        );
+        assert_eq!(chunks[1].start, chunks[0].end);
+        assert_eq!(chunks[1].end, range.end);
+        assert!(chunks.iter().all(|chunk| chunk.timestep == range.timestep));
+    }
+
+    #[test]
+    fn query_parameters_include_timestep_in_minutes() {
+        assert_eq!(
+            time_range(24).query_parameters(),
+            "&starttime=2024-06-01T00:00:00Z&endtime=2024-06-02T00:00:00Z&timestep=60"
+        );
+        let range = TimeRange {
+            timestep: None,
+            ..time_range(24)
+        };
+        assert!(!range.query_parameters().contains("timestep"));
+    }
 }
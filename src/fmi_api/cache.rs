@@ -0,0 +1,216 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use super::FmiError;
+
+/// FMI stations report every 10 minutes.
+const OBSERVATION_INTERVAL_MINUTES: i64 = 10;
+
+/// How long after the observation time FMI usually has the observation
+/// available.
+const PUBLICATION_DELAY_MINUTES: i64 = 5;
+
+/// How long a failed fetch is served to later requests, so that callers
+/// queued behind a fetch that timed out do not each wait for their own.
+const FAILURE_TTL_SECONDS: i64 = 30;
+
+struct CacheEntry<T, E> {
+    result: Result<T, E>,
+    expires_at: DateTime<Utc>,
+}
+
+type Slot<T, E> = Arc<tokio::sync::Mutex<Option<CacheEntry<T, E>>>>;
+
+/// Per station cache where concurrent requests for the same station share a
+/// single upstream fetch. Entries expire when FMI is expected to have
+/// published the next observation, or after a fixed time for data that is
+/// not tied to the observation cadence, such as forecasts. Errors are kept
+/// for `FAILURE_TTL_SECONDS`, so everyone waiting on a failed fetch gets
+/// its error and the next request after that tries again.
+pub struct SingleFlightCache<T, E = FmiError> {
+    slots: Mutex<HashMap<String, Slot<T, E>>>,
+    /// Until the next publication when not set
+    ttl: Option<Duration>,
+    failure_ttl: Duration,
+}
+
+impl<T, E> Default for SingleFlightCache<T, E> {
+    fn default() -> Self {
+        SingleFlightCache {
+            slots: Mutex::new(HashMap::new()),
+            ttl: None,
+            failure_ttl: Duration::seconds(FAILURE_TTL_SECONDS),
+        }
+    }
+}
+
+impl<T, E> SingleFlightCache<T, E> {
+    /// A cache whose entries expire `ttl` after they were fetched.
+    pub fn with_ttl(ttl: Duration) -> Self {
+        SingleFlightCache {
+            slots: Mutex::new(HashMap::new()),
+            ttl: Some(ttl),
+            failure_ttl: Duration::seconds(FAILURE_TTL_SECONDS),
+        }
+    }
+
+    fn expires_at(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self.ttl {
+            Some(ttl) => now + ttl,
+            None => next_publication_time(now),
+        }
+    }
+}
+
+impl<T: Clone, E: Clone> SingleFlightCache<T, E> {
+    /// Returns the cached value of `key`, or runs `fetch` to get a new one.
+    /// While one caller is fetching, the others wait for its result.
+    pub async fn get_or_fetch<F, Fut>(&self, key: &str, fetch: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let slot = self.slot(key);
+        let mut entry = slot.lock().await;
+        let now = Utc::now();
+        if let Some(entry) = entry.as_ref().filter(|entry| entry.expires_at > now) {
+            return entry.result.clone();
+        }
+
+        let result = fetch().await;
+        let now = Utc::now();
+        let expires_at = match result {
+            Ok(_) => self.expires_at(now),
+            Err(_) => now + self.failure_ttl,
+        };
+        *entry = Some(CacheEntry {
+            result: result.clone(),
+            expires_at,
+        });
+        result
+    }
+
+    /// Stores a value fetched by the background poller. It is kept one
+    /// observation interval longer than a fetched value, so the poller
+    /// replaces it before it expires and page loads never wait on FMI.
+    pub async fn insert(&self, key: &str, value: T) {
+        let slot = self.slot(key);
+        let expires_at =
+            self.expires_at(Utc::now()) + Duration::minutes(OBSERVATION_INTERVAL_MINUTES);
+        *slot.lock().await = Some(CacheEntry {
+            result: Ok(value),
+            expires_at,
+        });
+    }
+
+    fn slot(&self, key: &str) -> Slot<T, E> {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots.entry(key.to_string()).or_default().clone()
+    }
+}
+
+/// The first time after `now` when a new observation should be available,
+/// e.g. 12:15 for 12:07, when the 12:10 observation is published.
+pub fn next_publication_time(now: DateTime<Utc>) -> DateTime<Utc> {
+    let interval = Duration::minutes(OBSERVATION_INTERVAL_MINUTES);
+    let delay = Duration::minutes(PUBLICATION_DELAY_MINUTES);
+    let latest_observation_time = (now - delay)
+        .duration_trunc(interval)
+        .unwrap_or(now - delay);
+    latest_observation_time + interval + delay
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use futures::future::join_all;
+    use reqwest::StatusCode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fetches `result` after a short wait, counting the fetches.
+    async fn fetch(fetches: &AtomicUsize, result: Result<i32, FmiError>) -> Result<i32, FmiError> {
+        fetches.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        result
+    }
+
+    #[tokio::test]
+    async fn concurrent_calls_share_one_fetch() {
+        let cache = SingleFlightCache::default();
+        let fetches = AtomicUsize::new(0);
+        let results =
+            join_all((0..10).map(|_| cache.get_or_fetch("harmaja", || fetch(&fetches, Ok(1)))))
+                .await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|result| matches!(result, Ok(1))));
+    }
+
+    #[tokio::test]
+    async fn concurrent_calls_share_one_failure() {
+        let cache = SingleFlightCache::<i32>::default();
+        let fetches = AtomicUsize::new(0);
+        let results = join_all((0..10).map(|_| {
+            cache.get_or_fetch("harmaja", || {
+                fetch(&fetches, Err(FmiError::HttpStatus(StatusCode::BAD_GATEWAY)))
+            })
+        }))
+        .await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert!(results
+            .iter()
+            .all(|result| matches!(result, Err(FmiError::HttpStatus(_)))));
+    }
+
+    #[tokio::test]
+    async fn expired_entry_is_fetched_again() {
+        let cache = SingleFlightCache::with_ttl(Duration::zero());
+        let fetches = AtomicUsize::new(0);
+        assert!(matches!(
+            cache
+                .get_or_fetch("harmaja", || fetch(&fetches, Ok(1)))
+                .await,
+            Ok(1)
+        ));
+        assert!(matches!(
+            cache
+                .get_or_fetch("harmaja", || fetch(&fetches, Ok(2)))
+                .await,
+            Ok(2)
+        ));
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn failure_is_not_served_as_a_value() {
+        let cache = SingleFlightCache {
+            failure_ttl: Duration::zero(),
+            ..SingleFlightCache::default()
+        };
+        let fetches = AtomicUsize::new(0);
+        assert!(cache
+            .get_or_fetch("harmaja", || fetch(&fetches, Err(FmiError::NoData)))
+            .await
+            .is_err());
+        assert!(matches!(
+            cache
+                .get_or_fetch("harmaja", || fetch(&fetches, Ok(1)))
+                .await,
+            Ok(1)
+        ));
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn next_publication_is_five_minutes_after_the_next_observation() {
+        let time = |minute, second| {
+            Utc.with_ymd_and_hms(2024, 6, 1, 12, minute, second)
+                .unwrap()
+        };
+        assert_eq!(next_publication_time(time(7, 0)), time(15, 0));
+        assert_eq!(next_publication_time(time(15, 0)), time(25, 0));
+        assert_eq!(next_publication_time(time(3, 30)), time(5, 0));
+    }
+}
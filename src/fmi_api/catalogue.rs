@@ -0,0 +1,218 @@
+use chrono::{DateTime, Utc};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::{get_xml, malformed_xml, parse_coordinates, parse_exception_report, FmiError};
+
+/// A station as described in FMI's station catalogue.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueStation {
+    pub fmis_id: i32,
+    /// The official name, in Finnish
+    pub name: String,
+    /// Only set when the catalogue has a name marked as Swedish
+    pub swedish_name: Option<String>,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// The networks the station belongs to, e.g. `Automaattinen sääasema`
+    pub station_types: Vec<String>,
+    /// Whether the station's operational period is still going on
+    pub active: bool,
+}
+
+/// Fetches every station of the `fmi::ef::stations` catalogue, keyed by
+/// fmisid.
+pub async fn fetch_station_catalogue(
+    base_url: &str,
+    reqwest_client: &ClientWithMiddleware,
+) -> Result<HashMap<i32, CatalogueStation>, FmiError> {
+    println!("Fetching station catalogue");
+    let url = format!(
+        "{}?service=WFS&version=2.0.0&request=getFeature&storedquery_id=fmi::ef::stations",
+        base_url
+    );
+    let xml = get_xml(&url, reqwest_client).await?;
+    parse_station_catalogue(&xml, Utc::now())
+}
+
+/// A station while its `ef:EnvironmentalMonitoringFacility` is being read.
+#[derive(Default)]
+struct Facility {
+    fmis_id: Option<i32>,
+    name: Option<String>,
+    swedish_name: Option<String>,
+    coordinates: Option<(f64, f64)>,
+    station_types: Vec<String>,
+    end_time: Option<DateTime<Utc>>,
+}
+
+impl Facility {
+    fn into_station(self, now: DateTime<Utc>) -> Option<CatalogueStation> {
+        let (latitude, longitude) = self.coordinates?;
+        Some(CatalogueStation {
+            fmis_id: self.fmis_id?,
+            name: self.name?,
+            swedish_name: self.swedish_name,
+            latitude,
+            longitude,
+            station_types: self.station_types,
+            // An ongoing period ends at `indeterminatePosition="now"`
+            active: !matches!(self.end_time, Some(end_time) if end_time <= now),
+        })
+    }
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Option<String> {
+    e.try_get_attribute(name)
+        .ok()
+        .flatten()
+        .and_then(|attribute| attribute.unescape_value().ok())
+        .map(|value| value.to_string())
+}
+
+/// Parses a `fmi::ef::stations` response. Facilities without an fmisid, a
+/// name or a location are left out.
+pub fn parse_station_catalogue(
+    xml: &str,
+    now: DateTime<Utc>,
+) -> Result<HashMap<i32, CatalogueStation>, FmiError> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut stations = HashMap::new();
+    let mut facility: Option<Facility> = None;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) if e.local_name().as_ref() == b"ExceptionReport" => {
+                return Err(parse_exception_report(xml).unwrap_or(FmiError::NoData));
+            }
+            Ok(Event::Start(e)) if e.name().as_ref() == b"ef:EnvironmentalMonitoringFacility" => {
+                facility = Some(Facility::default());
+            }
+            Ok(Event::Start(e)) => {
+                let Some(current) = facility.as_mut() else {
+                    buf.clear();
+                    continue;
+                };
+                match e.name().as_ref() {
+                    b"gml:identifier" => {
+                        let code_space = attribute(&e, "codeSpace").unwrap_or_default();
+                        match reader.read_text(e.name()) {
+                            Ok(text) if code_space.ends_with("/fmisid") => {
+                                current.fmis_id = text.trim().parse().ok();
+                            }
+                            Ok(_) => (),
+                            Err(e) => println!("Failed to read text: {}", e),
+                        }
+                    }
+                    b"gml:name" => {
+                        let code_space = attribute(&e, "codeSpace").unwrap_or_default();
+                        let language = attribute(&e, "xml:lang");
+                        match reader.read_text(e.name()) {
+                            Ok(text) if code_space.ends_with("/name") => {
+                                match language.as_deref() {
+                                    Some("sv" | "swe") => {
+                                        current.swedish_name = Some(text.to_string())
+                                    }
+                                    _ => current.name = Some(text.to_string()),
+                                }
+                            }
+                            Ok(_) => (),
+                            Err(e) => println!("Failed to read text: {}", e),
+                        }
+                    }
+                    b"gml:pos" => match reader.read_text(e.name()) {
+                        Ok(text) => current.coordinates = parse_coordinates(&text),
+                        Err(e) => println!("Failed to read text: {}", e),
+                    },
+                    b"gml:endPosition" => match reader.read_text(e.name()) {
+                        Ok(text) => current.end_time = text.parse().ok(),
+                        Err(e) => println!("Failed to read text: {}", e),
+                    },
+                    _ => (),
+                }
+            }
+            Ok(Event::Empty(e)) if e.name().as_ref() == b"ef:belongsTo" => {
+                if let (Some(current), Some(title)) =
+                    (facility.as_mut(), attribute(&e, "xlink:title"))
+                {
+                    current.station_types.push(title);
+                }
+            }
+            Ok(Event::End(e)) if e.name().as_ref() == b"ef:EnvironmentalMonitoringFacility" => {
+                if let Some(station) = facility.take().and_then(|f| f.into_station(now)) {
+                    stations.insert(station.fmis_id, station);
+                }
+            }
+            Err(e) => return Err(malformed_xml(reader.buffer_position(), e)),
+            Ok(Event::Eof) => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    if stations.is_empty() {
+        return Err(FmiError::NoData);
+    }
+    Ok(stations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const STATIONS: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/stations.xml"
+    ));
+
+    fn catalogue() -> HashMap<i32, CatalogueStation> {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        parse_station_catalogue(STATIONS, now).unwrap()
+    }
+
+    #[test]
+    fn keys_stations_by_fmisid_from_its_code_space() {
+        let catalogue = catalogue();
+        let mut fmis_ids: Vec<i32> = catalogue.keys().copied().collect();
+        fmis_ids.sort();
+        // The facility with only an lpnn identifier is left out
+        assert_eq!(fmis_ids, vec![100996, 101005]);
+    }
+
+    #[test]
+    fn reads_names_location_and_networks() {
+        let harmaja = &catalogue()[&100996];
+        assert_eq!(harmaja.name, "Helsinki Harmaja");
+        assert_eq!(harmaja.swedish_name.as_deref(), Some("Helsingfors Gråhara"));
+        assert_eq!((harmaja.latitude, harmaja.longitude), (60.10512, 24.97539));
+        assert_eq!(
+            harmaja.station_types,
+            vec!["Automaattinen sääasema", "Sadeasema"]
+        );
+
+        let isosaari = &catalogue()[&101005];
+        assert_eq!(isosaari.name, "Helsinki Isosaari");
+        assert_eq!(isosaari.swedish_name, None);
+    }
+
+    #[test]
+    fn station_is_active_until_its_period_ends() {
+        let catalogue = catalogue();
+        // `indeterminatePosition="now"`
+        assert!(catalogue[&100996].active);
+        // Ended in 2020
+        assert!(!catalogue[&101005].active);
+
+        let before_end = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let catalogue = parse_station_catalogue(STATIONS, before_end).unwrap();
+        assert!(catalogue[&101005].active);
+    }
+}
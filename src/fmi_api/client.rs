@@ -0,0 +1,159 @@
+use http::header::{HeaderName, HeaderValue};
+use http::Extensions;
+use http_cache_reqwest::{CACacheManager, Cache, CacheMode, HttpCache, HttpCacheOptions};
+use reqwest::{Client, Request, Response};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Middleware, Next};
+use reqwest_retry::{policies::ExponentialBackoff, Jitter, RetryTransientMiddleware};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// Counts of the requests sent to FMI since startup. Responses served from
+/// the HTTP cache are not counted.
+#[derive(Debug, Default)]
+pub struct RequestMetrics {
+    requests: AtomicU64,
+    attempts: AtomicU64,
+    failed_requests: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestMetricsSnapshot {
+    pub requests: u64,
+    /// Attempts made after the first one of a request
+    pub retries: u64,
+    /// Requests that still failed after every retry
+    pub failed_requests: u64,
+}
+
+impl RequestMetrics {
+    pub fn snapshot(&self) -> RequestMetricsSnapshot {
+        let requests = self.requests.load(Ordering::Relaxed);
+        RequestMetricsSnapshot {
+            requests,
+            retries: self
+                .attempts
+                .load(Ordering::Relaxed)
+                .saturating_sub(requests),
+            failed_requests: self.failed_requests.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Sits outside the retry layer, so it sees every request once.
+struct CountRequests(Arc<RequestMetrics>);
+
+#[async_trait::async_trait]
+impl Middleware for CountRequests {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        self.0.requests.fetch_add(1, Ordering::Relaxed);
+        let result = next.run(req, extensions).await;
+        let failed = match &result {
+            Ok(response) => !response.status().is_success(),
+            Err(_) => true,
+        };
+        if failed {
+            self.0.failed_requests.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+/// Sits inside the retry layer, so it sees every attempt.
+struct CountAttempts(Arc<RequestMetrics>);
+
+#[async_trait::async_trait]
+impl Middleware for CountAttempts {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        self.0.attempts.fetch_add(1, Ordering::Relaxed);
+        next.run(req, extensions).await
+    }
+}
+
+/// Adds the API key a mirror requires to every request, either as a header
+/// or as a query parameter.
+enum ApiKey {
+    Header(HeaderName, HeaderValue),
+    QueryParameter { name: String, key: String },
+}
+
+impl ApiKey {
+    fn from_config(config: &Config) -> Option<ApiKey> {
+        let key = config.fmi_api_key.as_ref()?;
+        Some(match &config.fmi_api_key_header {
+            Some(header) => ApiKey::Header(
+                HeaderName::from_bytes(header.as_bytes()).expect("Invalid API key header name"),
+                HeaderValue::from_str(key).expect("Invalid API key header value"),
+            ),
+            None => ApiKey::QueryParameter {
+                name: config.fmi_api_key_parameter.clone(),
+                key: key.clone(),
+            },
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for ApiKey {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        match self {
+            ApiKey::Header(name, value) => {
+                req.headers_mut().insert(name.clone(), value.clone());
+            }
+            ApiKey::QueryParameter { name, key } => {
+                req.url_mut().query_pairs_mut().append_pair(name, key);
+            }
+        }
+        next.run(req, extensions).await
+    }
+}
+
+/// Builds the client used for every FMI request. Responses are cached
+/// according to their cache headers. Timeouts, 5xx and 429 responses are
+/// retried with jittered exponential backoff, which is safe because every
+/// WFS request is an idempotent GET.
+pub fn build_client(config: &Config, metrics: Arc<RequestMetrics>) -> ClientWithMiddleware {
+    let client = Client::builder()
+        .connect_timeout(Duration::from_secs(config.fmi_connect_timeout_seconds))
+        .read_timeout(Duration::from_secs(config.fmi_read_timeout_seconds))
+        .build()
+        .expect("Failed to build HTTP client");
+    let retry_policy = ExponentialBackoff::builder()
+        .retry_bounds(Duration::from_millis(250), Duration::from_secs(4))
+        .jitter(Jitter::Bounded)
+        .build_with_max_retries(config.fmi_max_retries);
+
+    let builder = ClientBuilder::new(client)
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: CACacheManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .with(CountRequests(metrics.clone()))
+        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+        .with(CountAttempts(metrics));
+    // Added last so the key stays out of the cache keys
+    match ApiKey::from_config(config) {
+        Some(api_key) => builder.with(api_key).build(),
+        None => builder.build(),
+    }
+}
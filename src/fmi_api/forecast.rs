@@ -0,0 +1,62 @@
+use chrono::{Duration, DurationRound, Utc};
+use reqwest_middleware::ClientWithMiddleware;
+
+use super::{format_query_time, get_xml, FmiError, Records, ResponseFormat};
+use crate::model::{ForecastStep, ParameterType};
+use crate::utils::map_degrees_to_compass;
+
+/// How far ahead the forecast is fetched. HARMONIE runs reach a bit over two
+/// days.
+const FORECAST_HOURS: i64 = 48;
+
+/// Fetches the hourly HARMONIE point forecast for the next
+/// `FORECAST_HOURS` hours.
+pub async fn fetch_forecast(
+    (weather_station_location, url): (&str, &str),
+    format: ResponseFormat,
+    reqwest_client: &ClientWithMiddleware,
+) -> Result<Vec<ForecastStep>, FmiError> {
+    println!("Fetching forecast for {}", weather_station_location);
+    let xml = get_xml(&with_forecast_range(url), reqwest_client).await?;
+    parse_forecast(&xml, format)
+}
+
+/// Adds an hourly time range from the current hour to `FORECAST_HOURS`
+/// ahead to a forecast query, so the steps fall on whole hours.
+pub(super) fn with_forecast_range(url: &str) -> String {
+    let now = Utc::now();
+    let now = now.duration_trunc(Duration::hours(1)).unwrap_or(now);
+    format!(
+        "{}&starttime={}&endtime={}&timestep=60",
+        url,
+        format_query_time(now),
+        format_query_time(now + Duration::hours(FORECAST_HOURS))
+    )
+}
+
+/// Parses a `fmi::forecast::harmonie::surface::point` response into time
+/// steps ordered by time.
+pub fn parse_forecast(xml: &str, format: ResponseFormat) -> Result<Vec<ForecastStep>, FmiError> {
+    let records = Records::from_raw(format.parse_raw_observations(xml)?);
+    let steps: Vec<ForecastStep> = records
+        .iter()
+        .map(|(time, record)| {
+            let value = |parameter| record.get(&parameter).copied();
+            let wind_direction_degrees = value(ParameterType::WindDirection).map(|d| d as f32);
+            ForecastStep {
+                time: *time,
+                temperature_celsius: value(ParameterType::Temperature),
+                wind_speed_meters_per_second: value(ParameterType::WindSpeed),
+                wind_gust_speed_meters_per_second: value(ParameterType::WindGustSpeed),
+                wind_direction_degrees,
+                wind_direction_compass: wind_direction_degrees
+                    .and_then(map_degrees_to_compass::convert),
+            }
+        })
+        .collect();
+
+    if steps.is_empty() {
+        return Err(FmiError::NoData);
+    }
+    Ok(steps)
+}
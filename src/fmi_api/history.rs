@@ -0,0 +1,253 @@
+use chrono::{DateTime, Days, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Europe::Helsinki;
+use futures::join;
+use reqwest_middleware::ClientWithMiddleware;
+use std::collections::BTreeMap;
+
+use super::{fetch_xml, parse_observation_series, FmiError, ResponseFormat};
+use crate::model::{
+    DailySummary, HourlyWind, Observation, ObservationSeries, ParameterType, TimeRange,
+    WeeklySummary,
+};
+
+/// Number of days in the summary, including today.
+pub const SUMMARY_DAYS: u64 = 7;
+
+/// Fetches the daily and hourly aggregates of the last `SUMMARY_DAYS` days.
+pub async fn fetch_weekly_summary(
+    weather_station_location: &str,
+    (daily_url, hourly_url): (&str, &str),
+    reqwest_client: &ClientWithMiddleware,
+) -> Result<WeeklySummary, FmiError> {
+    let (daily_range, hourly_range) = summary_ranges(Utc::now());
+    let (daily, hourly) = join!(
+        fetch_xml(
+            (weather_station_location, daily_url),
+            daily_range,
+            reqwest_client
+        ),
+        fetch_xml(
+            (weather_station_location, hourly_url),
+            hourly_range,
+            reqwest_client
+        )
+    );
+    parse_weekly_summary(&daily?, &hourly?)
+}
+
+/// The daily and hourly query ranges, from the start of the first day of
+/// the summary in Helsinki until `now`. FMI includes the value at the start
+/// time, and the hourly value there would describe the last hour of the day
+/// before, so the hourly range starts at the end of the first hour.
+/// The aggregates come at their own resolution, so no timestep is set.
+fn summary_ranges(now: DateTime<Utc>) -> (TimeRange, TimeRange) {
+    let first_day = now.with_timezone(&Helsinki).date_naive() - Days::new(SUMMARY_DAYS - 1);
+    let start = Helsinki
+        .from_local_datetime(&first_day.and_time(Default::default()))
+        .earliest()
+        .map_or(now - Duration::days(SUMMARY_DAYS as i64), |start| {
+            start.with_timezone(&Utc)
+        });
+    let daily = TimeRange {
+        start,
+        end: now,
+        timestep: None,
+    };
+    let hourly = TimeRange {
+        start: start + Duration::hours(1),
+        ..daily
+    };
+    (daily, hourly)
+}
+
+/// A station without the aggregates, e.g. one that measures no
+/// temperature, gets an empty series instead of failing the summary.
+fn parse_series_or_empty(documents: &[String]) -> Result<ObservationSeries, FmiError> {
+    match parse_observation_series(documents, ResponseFormat::Simple) {
+        Err(FmiError::NoData) => Ok(Default::default()),
+        result => result,
+    }
+}
+
+/// Combines the responses of `daily::simple` and `hourly::simple` queries
+/// into one summary per day. Daily values are stamped at midnight UTC of
+/// their day, hourly values at the end of their hour.
+pub fn parse_weekly_summary(
+    daily_documents: &[String],
+    hourly_documents: &[String],
+) -> Result<WeeklySummary, FmiError> {
+    let daily = parse_series_or_empty(daily_documents)?;
+    let hourly = parse_series_or_empty(hourly_documents)?;
+
+    let mut days: BTreeMap<NaiveDate, DailySummary> = BTreeMap::new();
+    for observation in daily.get(ParameterType::DailyMinTemperature) {
+        day(&mut days, observation.time.date_naive()).min_temperature_celsius = observation.value;
+    }
+    for observation in daily.get(ParameterType::DailyMaxTemperature) {
+        day(&mut days, observation.time.date_naive()).max_temperature_celsius = observation.value;
+    }
+    for (date, observations) in by_helsinki_date(hourly.get(ParameterType::HourlyMaxGustSpeed)) {
+        day(&mut days, date).max_gust_speed_meters_per_second = observations
+            .iter()
+            .filter_map(|observation| observation.value)
+            .max_by(|a, b| a.total_cmp(b));
+    }
+    for (date, observations) in by_helsinki_date(hourly.get(ParameterType::HourlyMeanWindSpeed)) {
+        let values: Vec<f64> = observations
+            .iter()
+            .filter_map(|observation| observation.value)
+            .collect();
+        day(&mut days, date).mean_wind_speed_meters_per_second =
+            (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64);
+    }
+
+    let mut hourly_wind: BTreeMap<DateTime<Utc>, HourlyWind> = BTreeMap::new();
+    for observation in hourly.get(ParameterType::HourlyMeanWindSpeed) {
+        hour(&mut hourly_wind, observation.time).mean_wind_speed_meters_per_second =
+            observation.value;
+    }
+    for observation in hourly.get(ParameterType::HourlyMaxGustSpeed) {
+        hour(&mut hourly_wind, observation.time).max_gust_speed_meters_per_second =
+            observation.value;
+    }
+
+    Ok(WeeklySummary {
+        days: days.into_values().collect(),
+        hourly_wind: hourly_wind.into_values().collect(),
+    })
+}
+
+fn day(days: &mut BTreeMap<NaiveDate, DailySummary>, date: NaiveDate) -> &mut DailySummary {
+    days.entry(date).or_insert_with(|| DailySummary {
+        date,
+        min_temperature_celsius: None,
+        max_temperature_celsius: None,
+        max_gust_speed_meters_per_second: None,
+        mean_wind_speed_meters_per_second: None,
+    })
+}
+
+fn hour(
+    hourly_wind: &mut BTreeMap<DateTime<Utc>, HourlyWind>,
+    time: DateTime<Utc>,
+) -> &mut HourlyWind {
+    hourly_wind.entry(time).or_insert_with(|| HourlyWind {
+        time,
+        mean_wind_speed_meters_per_second: None,
+        max_gust_speed_meters_per_second: None,
+    })
+}
+
+/// Groups hourly values by the Helsinki date of the hour they describe.
+fn by_helsinki_date(observations: &[Observation]) -> BTreeMap<NaiveDate, Vec<&Observation>> {
+    let mut by_date: BTreeMap<NaiveDate, Vec<&Observation>> = BTreeMap::new();
+    for observation in observations {
+        let start_of_hour = observation.time - Duration::hours(1);
+        by_date
+            .entry(start_of_hour.with_timezone(&Helsinki).date_naive())
+            .or_default()
+            .push(observation);
+    }
+    by_date
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DurationRound, NaiveTime};
+
+    /// A `simple` response with one value of `parameter` at each time.
+    fn simple_document(parameter: &str, times: &[DateTime<Utc>]) -> String {
+        let members: String = times
+            .iter()
+            .map(|time| {
+                format!(
+                    "<wfs:member><BsWfs:BsWfsElement gml:id=\"x\"><BsWfs:Location><gml:Point gml:id=\"p\"><gml:pos>60.1 22.0 </gml:pos></gml:Point></BsWfs:Location><BsWfs:Time>{}</BsWfs:Time><BsWfs:ParameterName>{}</BsWfs:ParameterName><BsWfs:ParameterValue>5</BsWfs:ParameterValue></BsWfs:BsWfsElement></wfs:member>\n",
+                    time.format("%Y-%m-%dT%H:%M:%SZ"),
+                    parameter
+                )
+            })
+            .collect();
+        format!(
+            "<?xml version=\"1.0\"?>\n<wfs:FeatureCollection xmlns:wfs=\"http://www.opengis.net/wfs/2.0\" xmlns:gml=\"http://www.opengis.net/gml/3.2\" xmlns:BsWfs=\"http://xml.fmi.fi/schema/wfs/2.0\">\n{}</wfs:FeatureCollection>",
+            members
+        )
+    }
+
+    /// Every time from `start` to `end`, both included, `step` apart.
+    fn times(start: DateTime<Utc>, end: DateTime<Utc>, step: Duration) -> Vec<DateTime<Utc>> {
+        let mut times = Vec::new();
+        let mut time = start;
+        while time <= end {
+            times.push(time);
+            time += step;
+        }
+        times
+    }
+
+    /// Answers the summary queries at `now` the way FMI does, with a value
+    /// at every hour of the hourly range and every midnight UTC of the
+    /// daily range.
+    fn summary_at(now: DateTime<Utc>) -> WeeklySummary {
+        let (daily_range, hourly_range) = summary_ranges(now);
+        let midnights = times(
+            daily_range.start.duration_trunc(Duration::days(1)).unwrap() + Duration::days(1),
+            daily_range.end,
+            Duration::days(1),
+        );
+        let hours = times(
+            hourly_range.start,
+            hourly_range.end.duration_trunc(Duration::hours(1)).unwrap(),
+            Duration::hours(1),
+        );
+        parse_weekly_summary(
+            &[simple_document("tmax", &midnights)],
+            &[simple_document("WS_PT1H_AVG", &hours)],
+        )
+        .unwrap()
+    }
+
+    fn assert_covers_summary_days(now: DateTime<Utc>) {
+        let summary = summary_at(now);
+        let today = now.with_timezone(&Helsinki).date_naive();
+        let dates: Vec<NaiveDate> = summary.days.iter().map(|day| day.date).collect();
+        assert_eq!(dates.len() as u64, SUMMARY_DAYS, "{:?}", dates);
+        assert_eq!(dates.first(), Some(&(today - Days::new(SUMMARY_DAYS - 1))));
+        assert_eq!(dates.last(), Some(&today));
+    }
+
+    #[test]
+    fn summary_covers_exactly_the_summary_days() {
+        assert_covers_summary_days(Utc.with_ymd_and_hms(2024, 6, 12, 9, 30, 0).unwrap());
+    }
+
+    #[test]
+    fn summary_covers_exactly_the_summary_days_across_daylight_saving_changes() {
+        // Clocks went forward on 31 March and back on 27 October 2024
+        assert_covers_summary_days(Utc.with_ymd_and_hms(2024, 4, 2, 9, 30, 0).unwrap());
+        assert_covers_summary_days(Utc.with_ymd_and_hms(2024, 10, 29, 9, 30, 0).unwrap());
+    }
+
+    #[test]
+    fn first_day_has_every_hour() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 12, 9, 30, 0).unwrap();
+        let (_, hourly_range) = summary_ranges(now);
+        let first_hour = hourly_range.start;
+        assert_eq!(
+            first_hour.with_timezone(&Helsinki).naive_local().time(),
+            NaiveTime::from_hms_opt(1, 0, 0).unwrap()
+        );
+        let summary = summary_at(now);
+        let first_day_hours = summary
+            .hourly_wind
+            .iter()
+            .filter(|hour| {
+                (hour.time - Duration::hours(1))
+                    .with_timezone(&Helsinki)
+                    .date_naive()
+                    == summary.days[0].date
+            })
+            .count();
+        assert_eq!(first_day_hours, 24);
+    }
+}
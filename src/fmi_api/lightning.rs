@@ -0,0 +1,204 @@
+use chrono::{DateTime, Duration, Utc};
+use reqwest_middleware::ClientWithMiddleware;
+
+use super::{get_latest_observations_xml, FmiError, ResponseFormat};
+use crate::model::LightningActivity;
+use crate::utils::distance;
+
+/// The time and coordinates of one lightning strike.
+pub type Strike = (DateTime<Utc>, (f64, f64));
+
+/// Fetches the strikes of the last hour. They are counted with
+/// `lightning_activity` when shown, so the counts are always relative to
+/// the current time, however long the strikes were cached.
+pub async fn fetch_lightning_strikes(
+    (weather_station_location, url): (&str, &str),
+    format: ResponseFormat,
+    reqwest_client: &ClientWithMiddleware,
+) -> Result<Vec<Strike>, FmiError> {
+    println!("Fetching lightning for {}", weather_station_location);
+    let xml = get_latest_observations_xml(url, reqwest_client).await?;
+    parse_lightning_strikes(&xml, format)
+}
+
+/// Counts the strikes of a `fmi::observations::lightning` response that are
+/// within `radius_kilometers` of the station. The query is made with a
+/// bounding box, so its corners may hold strikes outside the radius.
+pub fn parse_lightning_activity(
+    xml: &str,
+    format: ResponseFormat,
+    station_coordinates: (f64, f64),
+    radius_kilometers: f64,
+    now: DateTime<Utc>,
+) -> Result<LightningActivity, FmiError> {
+    let strikes = parse_lightning_strikes(xml, format)?;
+    Ok(lightning_activity(
+        &strikes,
+        station_coordinates,
+        radius_kilometers,
+        now,
+    ))
+}
+
+/// The time and coordinates of every strike of a
+/// `fmi::observations::lightning` response, so one response covering many
+/// stations is parsed only once.
+pub fn parse_lightning_strikes(xml: &str, format: ResponseFormat) -> Result<Vec<Strike>, FmiError> {
+    let observations = match format.parse_raw_observations(xml) {
+        Ok(observations) => observations,
+        // No strikes at all in the bounding box
+        Err(FmiError::NoData) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    Ok(observations
+        .iter()
+        .filter_map(|observation| Some((observation.time, observation.coordinates?)))
+        .collect())
+}
+
+/// Counts the strikes within `radius_kilometers` of the station.
+pub fn lightning_activity(
+    strikes: &[Strike],
+    station_coordinates: (f64, f64),
+    radius_kilometers: f64,
+    now: DateTime<Utc>,
+) -> LightningActivity {
+    // Every parameter of a strike is its own element, so one strike is
+    // identified by its time and location
+    let mut strikes: Vec<(DateTime<Utc>, f64)> = strikes
+        .iter()
+        .map(|(time, coordinates)| {
+            (
+                *time,
+                distance::kilometers(station_coordinates, *coordinates),
+            )
+        })
+        .filter(|(_, kilometers)| *kilometers <= radius_kilometers)
+        .collect();
+    strikes.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    strikes.dedup();
+
+    let count_since = |minutes: i64| {
+        strikes
+            .iter()
+            .filter(|(time, _)| *time >= now - Duration::minutes(minutes))
+            .count()
+    };
+    let strikes_last_60_minutes = count_since(60);
+
+    LightningActivity {
+        radius_kilometers,
+        nearby: strikes_last_60_minutes > 0,
+        strikes_last_30_minutes: count_since(30),
+        strikes_last_60_minutes,
+        nearest_strike_kilometers: strikes
+            .iter()
+            .map(|(_, kilometers)| *kilometers)
+            .min_by(|a, b| a.total_cmp(b)),
+        latest_strike: strikes.iter().map(|(time, _)| *time).max(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const HARMAJA: (f64, f64) = (60.1052, 24.9754);
+    const RADIUS_KILOMETERS: f64 = 20.0;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    fn minutes_ago(minutes: i64) -> DateTime<Utc> {
+        now() - Duration::minutes(minutes)
+    }
+
+    /// A `simple` response with the peak current of every strike.
+    fn simple_document(strikes: &[Strike]) -> String {
+        let members: String = strikes
+            .iter()
+            .map(|(time, (latitude, longitude))| {
+                format!(
+                    "<wfs:member><BsWfs:BsWfsElement gml:id=\"x\"><BsWfs:Location><gml:Point gml:id=\"p\"><gml:pos>{} {} </gml:pos></gml:Point></BsWfs:Location><BsWfs:Time>{}</BsWfs:Time><BsWfs:ParameterName>peak_current</BsWfs:ParameterName><BsWfs:ParameterValue>-12.0</BsWfs:ParameterValue></BsWfs:BsWfsElement></wfs:member>\n",
+                    latitude,
+                    longitude,
+                    time.format("%Y-%m-%dT%H:%M:%SZ")
+                )
+            })
+            .collect();
+        format!(
+            "<?xml version=\"1.0\"?>\n<wfs:FeatureCollection xmlns:wfs=\"http://www.opengis.net/wfs/2.0\" xmlns:gml=\"http://www.opengis.net/gml/3.2\" xmlns:BsWfs=\"http://xml.fmi.fi/schema/wfs/2.0\">\n{}</wfs:FeatureCollection>",
+            members
+        )
+    }
+
+    #[test]
+    fn leaves_out_strikes_in_the_corners_of_the_bounding_box() {
+        // The corner of the box the query is made with, as in
+        // `create_area_lightning_url`
+        let latitude_offset = RADIUS_KILOMETERS / 111.0;
+        let longitude_offset = latitude_offset / HARMAJA.0.to_radians().cos();
+        let corner = (HARMAJA.0 + latitude_offset, HARMAJA.1 + longitude_offset);
+        // Halfway to the northern edge
+        let inside = (HARMAJA.0 + latitude_offset / 2.0, HARMAJA.1);
+
+        let activity = lightning_activity(
+            &[(minutes_ago(5), corner), (minutes_ago(10), inside)],
+            HARMAJA,
+            RADIUS_KILOMETERS,
+            now(),
+        );
+        assert_eq!(activity.strikes_last_60_minutes, 1);
+        assert_eq!(activity.latest_strike, Some(minutes_ago(10)));
+        let nearest = activity.nearest_strike_kilometers.unwrap();
+        assert!((nearest - 10.0).abs() < 0.5, "{}", nearest);
+    }
+
+    #[test]
+    fn counts_the_last_30_and_60_minutes() {
+        let strikes = [
+            (minutes_ago(5), HARMAJA),
+            (minutes_ago(29), HARMAJA),
+            (minutes_ago(45), HARMAJA),
+            (minutes_ago(59), HARMAJA),
+            (minutes_ago(90), HARMAJA),
+        ];
+        let activity = lightning_activity(&strikes, HARMAJA, RADIUS_KILOMETERS, now());
+        assert_eq!(activity.strikes_last_30_minutes, 2);
+        assert_eq!(activity.strikes_last_60_minutes, 4);
+        assert!(activity.nearby);
+        assert_eq!(activity.latest_strike, Some(minutes_ago(5)));
+    }
+
+    #[test]
+    fn old_strikes_are_not_nearby() {
+        let activity = lightning_activity(
+            &[(minutes_ago(61), HARMAJA)],
+            HARMAJA,
+            RADIUS_KILOMETERS,
+            now(),
+        );
+        assert!(!activity.nearby);
+        assert_eq!(activity.strikes_last_60_minutes, 0);
+    }
+
+    #[test]
+    fn counts_a_strike_at_the_same_time_and_place_once() {
+        let strike = (minutes_ago(5), (60.11, 24.98));
+        let xml = simple_document(&[strike, strike, (minutes_ago(5), (60.12, 24.98))]);
+        let strikes = parse_lightning_strikes(&xml, ResponseFormat::Simple).unwrap();
+        assert_eq!(strikes.len(), 3);
+
+        let activity = lightning_activity(&strikes, HARMAJA, RADIUS_KILOMETERS, now());
+        assert_eq!(activity.strikes_last_60_minutes, 2);
+    }
+
+    #[test]
+    fn response_without_strikes_is_empty() {
+        let xml = simple_document(&[]);
+        let strikes = parse_lightning_strikes(&xml, ResponseFormat::Simple).unwrap();
+        assert!(strikes.is_empty());
+    }
+}
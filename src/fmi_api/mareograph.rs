@@ -0,0 +1,65 @@
+use reqwest_middleware::ClientWithMiddleware;
+
+use super::forecast::with_forecast_range;
+use super::{get_latest_observations_xml, get_xml, FmiError, Records, ResponseFormat};
+use crate::model::{ParameterType, SeaLevelData, SeaLevelForecastStep};
+
+pub async fn fetch_sea_level(
+    (mareograph_location, url): (&str, &str),
+    format: ResponseFormat,
+    reqwest_client: &ClientWithMiddleware,
+) -> Result<SeaLevelData, FmiError> {
+    println!("Fetching sea level for {}", mareograph_location);
+    let xml = get_latest_observations_xml(url, reqwest_client).await?;
+    parse_latest_sea_level(&xml, format)
+}
+
+/// Parses a `fmi::observations::mareograph` response into the latest sea
+/// level.
+pub fn parse_latest_sea_level(xml: &str, format: ResponseFormat) -> Result<SeaLevelData, FmiError> {
+    let records = Records::from_raw(format.parse_raw_observations(xml)?);
+
+    Ok(SeaLevelData {
+        time_of_observation: records.newest_time()?,
+        sea_level_n2000_millimeters: records.latest_reading(ParameterType::SeaLevelN2000),
+        sea_level_relative_to_mean_millimeters: records
+            .latest_reading(ParameterType::SeaLevelTheoreticalMean),
+    })
+}
+
+pub async fn fetch_sea_level_forecast(
+    (mareograph_location, url): (&str, &str),
+    format: ResponseFormat,
+    reqwest_client: &ClientWithMiddleware,
+) -> Result<Vec<SeaLevelForecastStep>, FmiError> {
+    println!("Fetching sea level forecast for {}", mareograph_location);
+    let xml = get_xml(&with_forecast_range(url), reqwest_client).await?;
+    parse_sea_level_forecast(&xml, format)
+}
+
+/// Parses a `fmi::forecast::oaas::sealevel::point` response into time steps
+/// ordered by time. The forecast is given in centimetres.
+pub fn parse_sea_level_forecast(
+    xml: &str,
+    format: ResponseFormat,
+) -> Result<Vec<SeaLevelForecastStep>, FmiError> {
+    let records = Records::from_raw(format.parse_raw_observations(xml)?);
+    let steps: Vec<SeaLevelForecastStep> = records
+        .iter()
+        .map(|(time, record)| {
+            let millimeters = |parameter| record.get(&parameter).map(|cm| cm * 10.0);
+            SeaLevelForecastStep {
+                time: *time,
+                sea_level_n2000_millimeters: millimeters(ParameterType::SeaLevelForecastN2000),
+                sea_level_relative_to_mean_millimeters: millimeters(
+                    ParameterType::SeaLevelForecast,
+                ),
+            }
+        })
+        .collect();
+
+    if steps.is_empty() {
+        return Err(FmiError::NoData);
+    }
+    Ok(steps)
+}
@@ -0,0 +1,245 @@
+use chrono::DateTime;
+use quick_xml::{events::Event, Reader};
+use std::collections::HashMap;
+
+use super::{malformed_xml, parse_coordinates, parse_exception_report, FmiError, RawObservation};
+use crate::model::ParameterType;
+
+/// Parses a `multipointcoverage` stored query response. Each observation
+/// member holds a `gmlcov:positions` block with one `lat lon unixtime` triple
+/// per row, a `gml:doubleOrNilReasonTupleList` block with one row of values
+/// per position, and the parameter names of the columns as `swe:field`
+/// elements after the data. The fmisid of each row is found by matching its
+/// coordinates against the `gml:Point` elements of the stations.
+pub(super) fn parse_raw_observations(xml: &str) -> Result<Vec<RawObservation>, FmiError> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut observations = Vec::new();
+    let mut positions = String::new();
+    let mut values = String::new();
+    let mut fields: Vec<Option<ParameterType>> = Vec::new();
+    let mut stations: HashMap<String, i32> = HashMap::new();
+    let mut current_point: Option<i32> = None;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) if e.local_name().as_ref() == b"ExceptionReport" => {
+                return Err(parse_exception_report(xml).unwrap_or(FmiError::NoData));
+            }
+            Ok(Event::Start(e)) => match e.name().as_ref() {
+                b"omso:GridSeriesObservation" => {
+                    positions.clear();
+                    values.clear();
+                    fields.clear();
+                    stations.clear();
+                }
+                b"gml:Point" => {
+                    current_point = e
+                        .try_get_attribute("gml:id")
+                        .ok()
+                        .flatten()
+                        .and_then(|attribute| attribute.unescape_value().ok())
+                        .and_then(|id| id.strip_prefix("point-")?.parse().ok());
+                }
+                b"gml:pos" => match reader.read_text(e.name()) {
+                    Ok(text) => {
+                        if let Some(fmis_id) = current_point.take() {
+                            stations.insert(coordinate_key(&text), fmis_id);
+                        }
+                    }
+                    Err(e) => return Err(malformed_xml(reader.buffer_position(), e)),
+                },
+                b"gmlcov:positions" => match reader.read_text(e.name()) {
+                    Ok(text) => positions = text.to_string(),
+                    Err(e) => return Err(malformed_xml(reader.buffer_position(), e)),
+                },
+                b"gml:doubleOrNilReasonTupleList" => match reader.read_text(e.name()) {
+                    Ok(text) => values = text.to_string(),
+                    Err(e) => return Err(malformed_xml(reader.buffer_position(), e)),
+                },
+                _ => (),
+            },
+            Ok(Event::Empty(e)) if e.name().as_ref() == b"swe:field" => {
+                let name = e
+                    .try_get_attribute("name")
+                    .ok()
+                    .flatten()
+                    .and_then(|attribute| attribute.unescape_value().ok());
+                fields.push(name.and_then(|name| ParameterType::from_fmi_name(&name)));
+            }
+            Ok(Event::End(e)) if e.name().as_ref() == b"omso:GridSeriesObservation" => {
+                observations.extend(combine(&positions, &values, &fields, &stations)?);
+            }
+            Err(e) => return Err(malformed_xml(reader.buffer_position(), e)),
+            Ok(Event::Eof) => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    Ok(observations)
+}
+
+fn coordinate_key(coordinates: &str) -> String {
+    coordinates
+        .split_whitespace()
+        .take(2)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Pairs every row of values with the time and station of the matching
+/// position.
+fn combine(
+    positions: &str,
+    values: &str,
+    fields: &[Option<ParameterType>],
+    stations: &HashMap<String, i32>,
+) -> Result<Vec<RawObservation>, FmiError> {
+    if fields.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let positions: Vec<&str> = positions.split_whitespace().collect();
+    let values: Vec<&str> = values.split_whitespace().collect();
+    // Every position is a `lat lon unixtime` triple with one row of values
+    let rows = positions.len() / 3;
+    if positions.len() != rows * 3 || values.len() != rows * fields.len() {
+        return Err(FmiError::MalformedXml(format!(
+            "{} positions but {} values for {} fields",
+            rows,
+            values.len(),
+            fields.len()
+        )));
+    }
+
+    let mut observations = Vec::new();
+    for (position, row) in positions.chunks(3).zip(values.chunks(fields.len())) {
+        let time = match position
+            .get(2)
+            .and_then(|seconds| seconds.parse::<i64>().ok())
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        {
+            Some(time) => time,
+            None => {
+                println!("Failed to parse position: {:?}", position);
+                continue;
+            }
+        };
+        let fmis_id = position
+            .get(..2)
+            .and_then(|coordinates| stations.get(&coordinates.join(" ")))
+            .copied();
+        for (parameter, value) in fields.iter().zip(row) {
+            if let Some(parameter) = parameter {
+                observations.push(RawObservation {
+                    fmis_id,
+                    coordinates: parse_coordinates(&position.join(" ")),
+                    time,
+                    parameter: *parameter,
+                    value: value.to_string(),
+                });
+            }
+        }
+    }
+    Ok(observations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response for stations 100947 and 100908 with two parameters.
+    fn document(positions: &str, values: &str) -> String {
+        format!(
+            r#"<wfs:FeatureCollection xmlns:wfs="http://www.opengis.net/wfs/2.0" xmlns:gml="http://www.opengis.net/gml/3.2" xmlns:omso="http://inspire.ec.europa.eu/schemas/omso/3.0" xmlns:gmlcov="http://www.opengis.net/gmlcov/1.0" xmlns:swe="http://www.opengis.net/swe/2.0">
+<wfs:member><omso:GridSeriesObservation gml:id="obs-1">
+<gml:MultiPoint>
+<gml:pointMember><gml:Point gml:id="point-100947"><gml:name>Turku Rajakari</gml:name><gml:pos>60.37750 22.09630 </gml:pos></gml:Point></gml:pointMember>
+<gml:pointMember><gml:Point gml:id="point-100908"><gml:name>Parainen Utö</gml:name><gml:pos>59.77909 21.37479 </gml:pos></gml:Point></gml:pointMember>
+</gml:MultiPoint>
+<gmlcov:positions>{}</gmlcov:positions>
+<gml:doubleOrNilReasonTupleList>{}</gml:doubleOrNilReasonTupleList>
+<swe:DataRecord>
+<swe:field name="Temperature"/>
+<swe:field name="WindSpeedMS"/>
+</swe:DataRecord>
+</omso:GridSeriesObservation></wfs:member>
+</wfs:FeatureCollection>"#,
+            positions, values
+        )
+    }
+
+    const POSITIONS: &str = "
+        60.37750 22.09630  1717242600
+        60.37750 22.09630  1717243200
+        59.77909 21.37479  1717242600
+        59.77909 21.37479  1717243200
+    ";
+
+    fn values_of(
+        observations: &[RawObservation],
+        fmis_id: i32,
+        parameter: ParameterType,
+    ) -> Vec<(i64, &str)> {
+        observations
+            .iter()
+            .filter(|observation| {
+                observation.fmis_id == Some(fmis_id) && observation.parameter == parameter
+            })
+            .map(|observation| (observation.time.timestamp(), observation.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn splits_rows_by_station() {
+        let xml = document(POSITIONS, "14.2 6.1  14.5 6.3  12.9 9.1  13.0 9.5");
+        let observations = parse_raw_observations(&xml).unwrap();
+
+        assert_eq!(observations.len(), 8);
+        assert_eq!(
+            values_of(&observations, 100947, ParameterType::Temperature),
+            vec![(1717242600, "14.2"), (1717243200, "14.5")]
+        );
+        assert_eq!(
+            values_of(&observations, 100908, ParameterType::WindSpeed),
+            vec![(1717242600, "9.1"), (1717243200, "9.5")]
+        );
+        assert_eq!(observations[0].coordinates, Some((60.3775, 22.0963)));
+    }
+
+    #[test]
+    fn keeps_nan_values_in_their_column() {
+        let xml = document(POSITIONS, "14.2 6.1  14.5 NaN  NaN 9.1  13.0 9.5");
+        let observations = parse_raw_observations(&xml).unwrap();
+
+        assert_eq!(
+            values_of(&observations, 100947, ParameterType::WindSpeed),
+            vec![(1717242600, "6.1"), (1717243200, "NaN")]
+        );
+        assert_eq!(
+            values_of(&observations, 100908, ParameterType::Temperature),
+            vec![(1717242600, "NaN"), (1717243200, "13.0")]
+        );
+    }
+
+    #[test]
+    fn rejects_row_count_mismatch() {
+        let xml = document(POSITIONS, "14.2 6.1  14.5 6.3  12.9 9.1");
+        assert!(matches!(
+            parse_raw_observations(&xml),
+            Err(FmiError::MalformedXml(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_partial_row() {
+        let xml = document(POSITIONS, "14.2 6.1  14.5 6.3  12.9 9.1  13.0 9.5 7.0");
+        assert!(matches!(
+            parse_raw_observations(&xml),
+            Err(FmiError::MalformedXml(_))
+        ));
+    }
+}
@@ -0,0 +1,497 @@
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use futures::future::join_all;
+use futures::join;
+use reqwest_middleware::ClientWithMiddleware;
+use std::collections::HashMap;
+
+use super::cache::SingleFlightCache;
+use super::forecast::fetch_forecast;
+use super::history::fetch_weekly_summary;
+use super::lightning::{
+    fetch_lightning_strikes, lightning_activity, parse_lightning_strikes, Strike,
+};
+use super::mareograph::{fetch_sea_level, fetch_sea_level_forecast};
+use super::wave::{fetch_sea_state, fetch_wave_forecast};
+use super::{
+    fetch_latest_weather_data_batch, fetch_xml, get_latest_observations_xml,
+    parse_latest_weather_data, parse_observation_series, FmiError, ResponseFormat,
+};
+use crate::config::Config;
+use crate::model::{
+    ForecastStep, LightningActivity, ObservationSeries, SeaLevelData, SeaLevelForecastStep,
+    SeaStateData, TimeRange, WaveForecastStep, WeatherData, WeeklySummary,
+};
+use crate::provider::{ProviderError, StationMetadata, WeatherProvider};
+use crate::stations::{create_area_lightning_url, MarineLocation, WeatherLocation};
+
+/// How long forecasts and the daily and hourly aggregates are cached, and
+/// how often the poller refreshes them. They change at most once an hour.
+pub const HOURLY_CACHE_MINUTES: i64 = 60;
+
+/// Most stations, wave buoys or mareographs the poller refreshes at a time.
+const MAX_CONCURRENT_REFRESHES: usize = 4;
+
+/// Observations, marine data and forecasts from the FMI open data WFS.
+/// Every source has its own cache, so concurrent page loads of a station
+/// share one fetch of each. The background poller keeps the caches filled
+/// through `refresh_observations` and `refresh_forecasts`. Wave buoys and
+/// mareographs are cached by their URL, as several stations share one.
+pub struct FmiProvider {
+    locations: HashMap<String, WeatherLocation>,
+    base_url: String,
+    reqwest_client: ClientWithMiddleware,
+    weather_cache: SingleFlightCache<WeatherData>,
+    sea_state_cache: SingleFlightCache<SeaStateData>,
+    sea_level_cache: SingleFlightCache<SeaLevelData>,
+    lightning_cache: SingleFlightCache<Vec<Strike>>,
+    forecast_cache: SingleFlightCache<Vec<ForecastStep>>,
+    sea_level_forecast_cache: SingleFlightCache<Vec<SeaLevelForecastStep>>,
+    wave_forecast_cache: SingleFlightCache<Vec<WaveForecastStep>>,
+    weekly_summary_cache: SingleFlightCache<WeeklySummary>,
+    /// How far back to look when a station has nothing within the last hour
+    stale_lookback: Duration,
+    lightning_radius_km: f64,
+}
+
+impl FmiProvider {
+    pub fn new(
+        locations: HashMap<String, WeatherLocation>,
+        reqwest_client: ClientWithMiddleware,
+        config: &Config,
+    ) -> FmiProvider {
+        let hourly = Duration::minutes(HOURLY_CACHE_MINUTES);
+        FmiProvider {
+            locations,
+            base_url: config.fmi_base_url.clone(),
+            reqwest_client,
+            weather_cache: SingleFlightCache::default(),
+            sea_state_cache: SingleFlightCache::default(),
+            sea_level_cache: SingleFlightCache::default(),
+            lightning_cache: SingleFlightCache::default(),
+            forecast_cache: SingleFlightCache::with_ttl(hourly),
+            sea_level_forecast_cache: SingleFlightCache::with_ttl(hourly),
+            wave_forecast_cache: SingleFlightCache::with_ttl(hourly),
+            weekly_summary_cache: SingleFlightCache::with_ttl(hourly),
+            stale_lookback: Duration::hours(config.stale_lookback_hours),
+            lightning_radius_km: config.lightning_radius_km,
+        }
+    }
+
+    fn location(&self, station_id: &str) -> Result<&WeatherLocation, FmiError> {
+        self.locations.get(station_id).ok_or(FmiError::NoData)
+    }
+
+    /// Fetches the latest observations, sea state, sea level and lightning
+    /// of every station into the caches.
+    pub async fn refresh_observations(&self) {
+        let weather_data =
+            fetch_latest_weather_data_batch(&self.locations, &self.base_url, &self.reqwest_client)
+                .await;
+        println!("Refreshed weather data of {} stations", weather_data.len());
+        for (station_id, data) in weather_data {
+            self.weather_cache.insert(&station_id, data).await;
+        }
+
+        let buoys = self.marine_locations(|location| location.wave_buoy.as_ref());
+        let mareographs = self.marine_locations(|location| location.mareograph.as_ref());
+        join!(
+            self.refresh_lightning(),
+            async {
+                for chunk in buoys.chunks(MAX_CONCURRENT_REFRESHES) {
+                    join_all(
+                        chunk
+                            .iter()
+                            .map(|(buoy, format)| self.refresh_sea_state(buoy, *format)),
+                    )
+                    .await;
+                }
+            },
+            async {
+                for chunk in mareographs.chunks(MAX_CONCURRENT_REFRESHES) {
+                    join_all(
+                        chunk.iter().map(|(mareograph, format)| {
+                            self.refresh_sea_level(mareograph, *format)
+                        }),
+                    )
+                    .await;
+                }
+            }
+        );
+    }
+
+    async fn refresh_sea_state(&self, buoy: &MarineLocation, format: ResponseFormat) {
+        let sea_state = fetch_sea_state(
+            (buoy.display_name.as_str(), buoy.url.as_str()),
+            format,
+            &self.reqwest_client,
+        )
+        .await;
+        store(&self.sea_state_cache, &buoy.url, sea_state).await;
+    }
+
+    async fn refresh_sea_level(&self, mareograph: &MarineLocation, format: ResponseFormat) {
+        let sea_level = fetch_sea_level(
+            (mareograph.display_name.as_str(), mareograph.url.as_str()),
+            format,
+            &self.reqwest_client,
+        )
+        .await;
+        store(&self.sea_level_cache, &mareograph.url, sea_level).await;
+    }
+
+    /// Fetches the lightning of every station from one query covering all
+    /// of them.
+    async fn refresh_lightning(&self) {
+        let Some(format) = self
+            .locations
+            .values()
+            .map(|location| location.format)
+            .next()
+        else {
+            return;
+        };
+        let points: Vec<(f64, f64)> = self
+            .locations
+            .values()
+            .map(|location| (location.latitude, location.longitude))
+            .collect();
+        let url =
+            create_area_lightning_url(&self.base_url, &points, self.lightning_radius_km, format);
+        let strikes = match get_latest_observations_xml(&url, &self.reqwest_client)
+            .await
+            .and_then(|xml| parse_lightning_strikes(&xml, format))
+        {
+            Ok(strikes) => strikes,
+            Err(e) => {
+                println!("Failed to refresh lightning: {}", e);
+                return;
+            }
+        };
+        for station_id in self.locations.keys() {
+            self.lightning_cache
+                .insert(station_id, strikes.clone())
+                .await;
+        }
+    }
+
+    /// Fetches the forecasts and the daily and hourly aggregates of every
+    /// station into the caches.
+    pub async fn refresh_forecasts(&self) {
+        let mareographs = self.marine_locations(|location| location.mareograph.as_ref());
+        let locations: Vec<_> = self.locations.iter().collect();
+        join!(
+            async {
+                for chunk in locations.chunks(MAX_CONCURRENT_REFRESHES) {
+                    join_all(chunk.iter().map(|(station_id, location)| {
+                        self.refresh_station_forecasts(station_id, location)
+                    }))
+                    .await;
+                }
+            },
+            async {
+                for chunk in mareographs.chunks(MAX_CONCURRENT_REFRESHES) {
+                    join_all(chunk.iter().map(|(mareograph, format)| {
+                        self.refresh_sea_level_forecast(mareograph, *format)
+                    }))
+                    .await;
+                }
+            }
+        );
+    }
+
+    async fn refresh_station_forecasts(&self, station_id: &str, location: &WeatherLocation) {
+        let forecast = fetch_forecast(
+            (station_id, location.forecast_url.as_str()),
+            location.format,
+            &self.reqwest_client,
+        )
+        .await;
+        store(&self.forecast_cache, station_id, forecast).await;
+
+        if let Some(url) = location.wave_forecast_url.as_deref() {
+            let wave_forecast =
+                fetch_wave_forecast((station_id, url), location.format, &self.reqwest_client).await;
+            store(&self.wave_forecast_cache, station_id, wave_forecast).await;
+        }
+
+        let weekly_summary = fetch_weekly_summary(
+            station_id,
+            (location.daily_url.as_str(), location.hourly_url.as_str()),
+            &self.reqwest_client,
+        )
+        .await;
+        store(&self.weekly_summary_cache, station_id, weekly_summary).await;
+    }
+
+    async fn refresh_sea_level_forecast(
+        &self,
+        mareograph: &MarineLocation,
+        format: ResponseFormat,
+    ) {
+        let Some(url) = mareograph.forecast_url.as_deref() else {
+            return;
+        };
+        let sea_level_forecast = fetch_sea_level_forecast(
+            (mareograph.display_name.as_str(), url),
+            format,
+            &self.reqwest_client,
+        )
+        .await;
+        store(
+            &self.sea_level_forecast_cache,
+            &mareograph.url,
+            sea_level_forecast,
+        )
+        .await;
+    }
+
+    /// Every wave buoy or mareograph of the stations once. Owned, as the
+    /// poller's tasks cannot borrow them.
+    fn marine_locations(
+        &self,
+        marine_location: fn(&WeatherLocation) -> Option<&MarineLocation>,
+    ) -> Vec<(MarineLocation, ResponseFormat)> {
+        let mut by_url: HashMap<&str, (MarineLocation, ResponseFormat)> = HashMap::new();
+        for location in self.locations.values() {
+            if let Some(marine) = marine_location(location) {
+                by_url.insert(marine.url.as_str(), (marine.clone(), location.format));
+            }
+        }
+        by_url.into_values().collect()
+    }
+}
+
+/// Stores a value fetched by the poller. After a failure the previous value
+/// is served until it expires.
+async fn store<T: Clone>(cache: &SingleFlightCache<T>, key: &str, result: Result<T, FmiError>) {
+    match result {
+        Ok(value) => cache.insert(key, value).await,
+        Err(e) => println!("Failed to refresh {}: {}", key, e),
+    }
+}
+
+fn metadata(station_id: &str, location: &WeatherLocation) -> StationMetadata {
+    let catalogue = location.catalogue.as_ref();
+    StationMetadata {
+        id: station_id.to_string(),
+        display_name: location.display_name.clone(),
+        latitude: location.latitude,
+        longitude: location.longitude,
+        official_name: catalogue.map(|station| station.name.clone()),
+        swedish_name: catalogue.and_then(|station| station.swedish_name.clone()),
+        station_types: catalogue.map_or_else(Vec::new, |station| station.station_types.clone()),
+        active: catalogue.map(|station| station.active),
+        wave_buoy: location
+            .wave_buoy
+            .as_ref()
+            .map(|buoy| buoy.display_name.clone()),
+        mareograph: location
+            .mareograph
+            .as_ref()
+            .map(|mareograph| mareograph.display_name.clone()),
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for FmiProvider {
+    fn stations(&self) -> Vec<StationMetadata> {
+        self.locations
+            .iter()
+            .map(|(station_id, location)| metadata(station_id, location))
+            .collect()
+    }
+
+    fn station(&self, station_id: &str) -> Option<StationMetadata> {
+        self.locations
+            .get(station_id)
+            .map(|location| metadata(station_id, location))
+    }
+
+    async fn fetch_latest(&self, station_id: &str) -> Result<WeatherData, ProviderError> {
+        let location = self.location(station_id)?;
+        self.weather_cache
+            .get_or_fetch(station_id, || async {
+                let documents = fetch_xml(
+                    (station_id, location.url.as_str()),
+                    TimeRange::latest(),
+                    &self.reqwest_client,
+                )
+                .await?;
+                match parse_latest_weather_data(&documents, location.format) {
+                    // A station that has stopped reporting still shows its
+                    // last observation, which is then flagged as stale
+                    Err(FmiError::NoData) => {
+                        let documents = fetch_xml(
+                            (station_id, location.url.as_str()),
+                            TimeRange::since(self.stale_lookback),
+                            &self.reqwest_client,
+                        )
+                        .await?;
+                        parse_latest_weather_data(&documents, location.format)
+                    }
+                    result => result,
+                }
+            })
+            .await
+            .map_err(ProviderError::from)
+    }
+
+    async fn fetch_series(
+        &self,
+        station_id: &str,
+        time_range: TimeRange,
+    ) -> Result<ObservationSeries, ProviderError> {
+        let location = self.location(station_id)?;
+        let documents = fetch_xml(
+            (station_id, location.url.as_str()),
+            time_range,
+            &self.reqwest_client,
+        )
+        .await?;
+        parse_observation_series(&documents, location.format).map_err(ProviderError::from)
+    }
+
+    async fn fetch_sea_state(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<SeaStateData>, ProviderError> {
+        let location = self.location(station_id)?;
+        let Some(buoy) = location.wave_buoy.as_ref() else {
+            return Ok(None);
+        };
+        self.sea_state_cache
+            .get_or_fetch(&buoy.url, || {
+                fetch_sea_state(
+                    (buoy.display_name.as_str(), buoy.url.as_str()),
+                    location.format,
+                    &self.reqwest_client,
+                )
+            })
+            .await
+            .map(Some)
+            .map_err(ProviderError::from)
+    }
+
+    async fn fetch_sea_level(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<SeaLevelData>, ProviderError> {
+        let location = self.location(station_id)?;
+        let Some(mareograph) = location.mareograph.as_ref() else {
+            return Ok(None);
+        };
+        self.sea_level_cache
+            .get_or_fetch(&mareograph.url, || {
+                fetch_sea_level(
+                    (mareograph.display_name.as_str(), mareograph.url.as_str()),
+                    location.format,
+                    &self.reqwest_client,
+                )
+            })
+            .await
+            .map(Some)
+            .map_err(ProviderError::from)
+    }
+
+    async fn fetch_forecast(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<Vec<ForecastStep>>, ProviderError> {
+        let location = self.location(station_id)?;
+        self.forecast_cache
+            .get_or_fetch(station_id, || {
+                fetch_forecast(
+                    (station_id, location.forecast_url.as_str()),
+                    location.format,
+                    &self.reqwest_client,
+                )
+            })
+            .await
+            .map(Some)
+            .map_err(ProviderError::from)
+    }
+
+    async fn fetch_sea_level_forecast(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<Vec<SeaLevelForecastStep>>, ProviderError> {
+        let location = self.location(station_id)?;
+        let Some((mareograph, url)) = location
+            .mareograph
+            .as_ref()
+            .and_then(|mareograph| Some((mareograph, mareograph.forecast_url.as_deref()?)))
+        else {
+            return Ok(None);
+        };
+        self.sea_level_forecast_cache
+            .get_or_fetch(&mareograph.url, || {
+                fetch_sea_level_forecast(
+                    (mareograph.display_name.as_str(), url),
+                    location.format,
+                    &self.reqwest_client,
+                )
+            })
+            .await
+            .map(Some)
+            .map_err(ProviderError::from)
+    }
+
+    async fn fetch_wave_forecast(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<Vec<WaveForecastStep>>, ProviderError> {
+        let location = self.location(station_id)?;
+        let Some(url) = location.wave_forecast_url.as_deref() else {
+            return Ok(None);
+        };
+        self.wave_forecast_cache
+            .get_or_fetch(station_id, || {
+                fetch_wave_forecast((station_id, url), location.format, &self.reqwest_client)
+            })
+            .await
+            .map(Some)
+            .map_err(ProviderError::from)
+    }
+
+    async fn fetch_lightning(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<LightningActivity>, ProviderError> {
+        let location = self.location(station_id)?;
+        let strikes = self
+            .lightning_cache
+            .get_or_fetch(station_id, || {
+                fetch_lightning_strikes(
+                    (station_id, location.lightning_url.as_str()),
+                    location.format,
+                    &self.reqwest_client,
+                )
+            })
+            .await?;
+        Ok(Some(lightning_activity(
+            &strikes,
+            (location.latitude, location.longitude),
+            self.lightning_radius_km,
+            Utc::now(),
+        )))
+    }
+
+    async fn fetch_weekly_summary(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<WeeklySummary>, ProviderError> {
+        let location = self.location(station_id)?;
+        self.weekly_summary_cache
+            .get_or_fetch(station_id, || {
+                fetch_weekly_summary(
+                    station_id,
+                    (location.daily_url.as_str(), location.hourly_url.as_str()),
+                    &self.reqwest_client,
+                )
+            })
+            .await
+            .map(Some)
+            .map_err(ProviderError::from)
+    }
+}
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use quick_xml::{events::Event, Reader};
+
+use super::{malformed_xml, parse_coordinates, parse_exception_report, FmiError, RawObservation};
+use crate::model::ParameterType;
+
+/// Parses a `simple` stored query response, where every value is wrapped in
+/// its own `BsWfs:BsWfsElement`.
+pub(super) fn parse_raw_observations(xml: &str) -> Result<Vec<RawObservation>, FmiError> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut observations = Vec::new();
+    let mut coordinates: Option<(f64, f64)> = None;
+    let mut time: Option<DateTime<Utc>> = None;
+    let mut parameter: Option<ParameterType> = None;
+    let mut value: Option<String> = None;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) if e.local_name().as_ref() == b"ExceptionReport" => {
+                return Err(parse_exception_report(xml).unwrap_or(FmiError::NoData));
+            }
+            Ok(Event::Start(e)) => match e.name().as_ref() {
+                b"BsWfs:BsWfsElement" => {
+                    coordinates = None;
+                    time = None;
+                    parameter = None;
+                    value = None;
+                }
+                b"gml:pos" => match reader.read_text(e.name()) {
+                    Ok(text) => coordinates = parse_coordinates(&text),
+                    Err(e) => println!("Failed to read text: {}", e),
+                },
+                b"BsWfs:Time" => match reader.read_text(e.name()) {
+                    Ok(text) => match text.parse::<DateTime<Utc>>() {
+                        Ok(dt) => time = Some(dt),
+                        Err(err) => println!("Failed to parse time: {}", err),
+                    },
+                    Err(e) => println!("Failed to read text: {}", e),
+                },
+                b"BsWfs:ParameterName" => match reader.read_text(e.name()) {
+                    Ok(text) => parameter = ParameterType::from_fmi_name(&text),
+                    Err(e) => println!("Failed to read text: {}", e),
+                },
+                b"BsWfs:ParameterValue" => match reader.read_text(e.name()) {
+                    Ok(text) => value = Some(text.to_string()),
+                    Err(e) => println!("Failed to read text: {}", e),
+                },
+                _ => (),
+            },
+            Ok(Event::End(e)) if e.name().as_ref() == b"BsWfs:BsWfsElement" => {
+                if let (Some(time), Some(parameter), Some(value)) =
+                    (time.take(), parameter.take(), value.take())
+                {
+                    observations.push(RawObservation {
+                        fmis_id: None,
+                        coordinates: coordinates.take(),
+                        time,
+                        parameter,
+                        value,
+                    });
+                }
+            }
+            Err(e) => return Err(malformed_xml(reader.buffer_position(), e)),
+            Ok(Event::Eof) => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    Ok(observations)
+}
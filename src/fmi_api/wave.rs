@@ -0,0 +1,71 @@
+use reqwest_middleware::ClientWithMiddleware;
+
+use super::forecast::with_forecast_range;
+use super::{get_latest_observations_xml, get_xml, FmiError, Records, ResponseFormat};
+use crate::model::{ParameterType, SeaStateData, WaveForecastStep};
+use crate::utils::map_degrees_to_compass;
+
+pub async fn fetch_sea_state(
+    (wave_buoy_location, url): (&str, &str),
+    format: ResponseFormat,
+    reqwest_client: &ClientWithMiddleware,
+) -> Result<SeaStateData, FmiError> {
+    println!("Fetching sea state for {}", wave_buoy_location);
+    let xml = get_latest_observations_xml(url, reqwest_client).await?;
+    parse_latest_sea_state(&xml, format)
+}
+
+/// Parses a `fmi::observations::wave` response into the latest sea state.
+pub fn parse_latest_sea_state(xml: &str, format: ResponseFormat) -> Result<SeaStateData, FmiError> {
+    let records = Records::from_raw(format.parse_raw_observations(xml)?);
+    let (wave_direction_degrees, wave_direction_compass) =
+        records.latest_direction(ParameterType::WaveDirection);
+
+    Ok(SeaStateData {
+        time_of_observation: records.newest_time()?,
+        significant_wave_height_meters: records.latest_reading(ParameterType::WaveHeight),
+        wave_period_seconds: records.latest_reading(ParameterType::WavePeriod),
+        wave_direction_degrees,
+        wave_direction_compass,
+        water_temperature_celsius: records.latest_reading(ParameterType::WaterTemperature),
+    })
+}
+
+pub async fn fetch_wave_forecast(
+    (weather_station_location, url): (&str, &str),
+    format: ResponseFormat,
+    reqwest_client: &ClientWithMiddleware,
+) -> Result<Vec<WaveForecastStep>, FmiError> {
+    println!("Fetching wave forecast for {}", weather_station_location);
+    let xml = get_xml(&with_forecast_range(url), reqwest_client).await?;
+    parse_wave_forecast(&xml, format)
+}
+
+/// Parses a `fmi::forecast::wam::point` response into time steps ordered by
+/// time.
+pub fn parse_wave_forecast(
+    xml: &str,
+    format: ResponseFormat,
+) -> Result<Vec<WaveForecastStep>, FmiError> {
+    let records = Records::from_raw(format.parse_raw_observations(xml)?);
+    let steps: Vec<WaveForecastStep> = records
+        .iter()
+        .map(|(time, record)| {
+            let value = |parameter| record.get(&parameter).copied();
+            let wave_direction_degrees = value(ParameterType::WaveDirection).map(|d| d as f32);
+            WaveForecastStep {
+                time: *time,
+                significant_wave_height_meters: value(ParameterType::WaveHeight),
+                wave_period_seconds: value(ParameterType::WavePeriod),
+                wave_direction_degrees,
+                wave_direction_compass: wave_direction_degrees
+                    .and_then(map_degrees_to_compass::convert),
+            }
+        })
+        .collect();
+
+    if steps.is_empty() {
+        return Err(FmiError::NoData);
+    }
+    Ok(steps)
+}
@@ -0,0 +1,24 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+/// How old the latest observation of a station is.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Freshness {
+    pub age_seconds: i64,
+    /// Whether the observation is older than the configured threshold
+    pub stale: bool,
+}
+
+/// Compares the time of an observation against `now`.
+pub fn check_freshness(
+    time_of_observation: DateTime<Utc>,
+    now: DateTime<Utc>,
+    stale_after_minutes: i64,
+) -> Freshness {
+    let age = now - time_of_observation;
+    Freshness {
+        age_seconds: age.num_seconds(),
+        stale: age > Duration::minutes(stale_after_minutes),
+    }
+}
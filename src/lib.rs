@@ -1,34 +1,92 @@
 #[macro_use]
 extern crate rocket;
-mod fmi_api;
+mod config;
+pub mod daylight;
+pub mod fmi_api;
+pub mod freshness;
+pub mod model;
+mod poller;
+pub mod provider;
 mod responder;
 mod routes;
 mod stations;
+pub mod trends;
 mod utils;
-use http_cache_reqwest::{CACacheManager, Cache, CacheMode, HttpCache, HttpCacheOptions};
-use reqwest::Client;
-use reqwest_middleware::ClientBuilder;
+use config::Config;
+use fmi_api::cache::SingleFlightCache;
+use fmi_api::catalogue::fetch_station_catalogue;
+use fmi_api::client::{build_client, RequestMetrics};
+use fmi_api::provider::FmiProvider;
+use fmi_api::ResponseFormat;
+use provider::{ProviderError, WeatherProvider};
+use reqwest_middleware::ClientWithMiddleware;
+use rocket::fairing::AdHoc;
 use rocket::fs::FileServer;
 use rocket_dyn_templates::Template;
-use stations::stations::{create_station_hashmap, WeatherLocation};
+use stations::{create_station_hashmap, stations_missing_from_catalogue};
+use std::collections::HashMap;
+use std::sync::Arc;
+use trends::Trends;
 #[launch]
 pub fn rocket() -> _ {
-    let urls = create_station_hashmap();
-    let reqwest_client = ClientBuilder::new(Client::new())
-        .with(Cache(HttpCache {
-            mode: CacheMode::Default,
-            manager: CACacheManager::default(),
-            options: HttpCacheOptions::default(),
-        }))
-        .build();
+    let rocket = rocket::build();
+    let config: Config = rocket.figment().extract().expect("Invalid configuration");
+    let metrics = Arc::new(RequestMetrics::default());
+    let reqwest_client = build_client(&config, metrics.clone());
 
-    rocket::build()
-        .manage(urls)
+    rocket
+        .manage(config)
         .manage(reqwest_client)
+        .manage(metrics)
+        .manage(SingleFlightCache::<Trends, ProviderError>::default())
+        .attach(stations_fairing())
         .mount(
             "/",
-            routes![routes::index::home_page, routes::weather::get_weather],
+            routes![
+                routes::index::home_page,
+                routes::metrics::get_metrics,
+                routes::weather::get_weather
+            ],
         )
         .attach(Template::fairing())
+        .attach(poller::fairing())
         .mount("/static", FileServer::from("./static"))
 }
+
+/// Builds the stations on ignite, so their coordinates can come from FMI's
+/// station catalogue. The built-in coordinates are used when the catalogue
+/// cannot be loaded.
+fn stations_fairing() -> AdHoc {
+    AdHoc::on_ignite("Stations", |rocket| async move {
+        let (Some(config), Some(reqwest_client)) = (
+            rocket.state::<Config>(),
+            rocket.state::<ClientWithMiddleware>().cloned(),
+        ) else {
+            panic!("Stations need the configuration and HTTP client");
+        };
+
+        let catalogue = if config.load_station_catalogue {
+            match fetch_station_catalogue(&config.fmi_base_url, &reqwest_client).await {
+                Ok(catalogue) => {
+                    for problem in stations_missing_from_catalogue(&catalogue) {
+                        println!("Station catalogue: {}", problem);
+                    }
+                    catalogue
+                }
+                Err(e) => {
+                    println!("Failed to fetch station catalogue: {}", e);
+                    HashMap::new()
+                }
+            }
+        } else {
+            HashMap::new()
+        };
+
+        let urls = create_station_hashmap(ResponseFormat::MultiPointCoverage, config, &catalogue);
+        let fmi_provider = Arc::new(FmiProvider::new(urls, reqwest_client, config));
+        // The poller refreshes the caches of the FMI provider, the routes
+        // only see the trait
+        let provider: Arc<dyn WeatherProvider> = fmi_provider.clone();
+        rocket.manage(fmi_provider).manage(provider)
+    })
+}
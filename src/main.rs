@@ -1,8 +1,4 @@
-use rannikkoasemat;
-use rocket;
-
 #[rocket::main]
-async fn main() -> Result<(), rocket::Error> {
+async fn main() {
     let _rocket = rannikkoasemat::rocket().launch().await;
-    Ok(())
 }
@@ -0,0 +1,226 @@
+use chrono::{DateTime, Duration, DurationRound, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A single value together with the time it was observed.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reading<T> {
+    pub value: T,
+    pub time_of_observation: DateTime<Utc>,
+}
+
+/// The newest observation record of a station. `time_of_observation` is the
+/// newest timestamp in the response; when a parameter is missing from it the
+/// field falls back to the parameter's own latest value and `complete` is
+/// false. Parameters without any value in the response, e.g. because the
+/// station has no sensor for them, are `None`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeatherData {
+    pub time_of_observation: DateTime<Utc>,
+    pub complete: bool,
+    pub wind_speed_meters_per_second: Option<Reading<f64>>,
+    pub temperature_celsius: Option<Reading<f64>>,
+    pub wind_direction_degrees: Option<Reading<f32>>,
+    pub wind_direction_compass: Option<&'static str>,
+    pub wind_gust_speed_meters_per_second: Option<Reading<f64>>,
+    pub pressure_hectopascals: Option<Reading<f64>>,
+    pub humidity_percent: Option<Reading<f64>>,
+    pub dew_point_celsius: Option<Reading<f64>>,
+    pub visibility_meters: Option<Reading<f64>>,
+    pub total_cloud_cover_oktas: Option<Reading<f64>>,
+    pub precipitation_one_hour_millimeters: Option<Reading<f64>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ParameterType {
+    WindSpeed,
+    Temperature,
+    WindDirection,
+    WindGustSpeed,
+    Pressure,
+    Humidity,
+    DewPoint,
+    Visibility,
+    TotalCloudCover,
+    Precipitation1h,
+    WaveHeight,
+    WavePeriod,
+    WaveDirection,
+    WaterTemperature,
+    SeaLevelN2000,
+    SeaLevelTheoreticalMean,
+    SeaLevelForecast,
+    SeaLevelForecastN2000,
+    LightningPeakCurrent,
+    DailyMinTemperature,
+    DailyMaxTemperature,
+    HourlyMeanWindSpeed,
+    HourlyMaxGustSpeed,
+}
+
+/// A single value of one parameter. Missing values, e.g. FMI's `NaN`, are
+/// `None`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Observation {
+    pub time: DateTime<Utc>,
+    pub value: Option<f64>,
+}
+
+/// Every observation in a response, grouped by parameter and ordered by time.
+#[derive(Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct ObservationSeries {
+    pub parameters: BTreeMap<ParameterType, Vec<Observation>>,
+}
+
+impl ObservationSeries {
+    pub fn get(&self, parameter: ParameterType) -> &[Observation] {
+        self.parameters
+            .get(&parameter)
+            .map_or(&[], |observations| observations.as_slice())
+    }
+}
+
+/// The time range and resolution of an observation query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Every observation the source has when not set, usually every 10
+    /// minutes
+    pub timestep: Option<Duration>,
+}
+
+impl TimeRange {
+    /// The observations of the last hour.
+    pub fn latest() -> TimeRange {
+        TimeRange::since(Duration::hours(1))
+    }
+
+    /// `lookback` until now with every observation.
+    pub fn since(lookback: Duration) -> TimeRange {
+        let now = Utc::now();
+        TimeRange {
+            start: now - lookback,
+            end: now,
+            timestep: None,
+        }
+    }
+
+    /// `lookback` until now with one observation every `timestep`, e.g. the
+    /// last 24 hours at hourly steps. Sources such as FMI count the steps
+    /// from the start time, so it is rounded down to a whole step.
+    pub fn last(lookback: Duration, timestep: Duration) -> TimeRange {
+        let now = Utc::now();
+        let start = now - lookback;
+        TimeRange {
+            start: start.duration_trunc(timestep).unwrap_or(start),
+            end: now,
+            timestep: Some(timestep),
+        }
+    }
+}
+
+/// One time step of a point forecast.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForecastStep {
+    pub time: DateTime<Utc>,
+    pub temperature_celsius: Option<f64>,
+    pub wind_speed_meters_per_second: Option<f64>,
+    pub wind_gust_speed_meters_per_second: Option<f64>,
+    pub wind_direction_degrees: Option<f32>,
+    pub wind_direction_compass: Option<&'static str>,
+}
+
+/// The latest sea state measured by a wave buoy.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeaStateData {
+    pub time_of_observation: DateTime<Utc>,
+    pub significant_wave_height_meters: Option<Reading<f64>>,
+    pub wave_period_seconds: Option<Reading<f64>>,
+    pub wave_direction_degrees: Option<Reading<f32>>,
+    pub wave_direction_compass: Option<&'static str>,
+    pub water_temperature_celsius: Option<Reading<f64>>,
+}
+
+/// One time step of the WAM wave model forecast at a point.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaveForecastStep {
+    pub time: DateTime<Utc>,
+    pub significant_wave_height_meters: Option<f64>,
+    pub wave_period_seconds: Option<f64>,
+    pub wave_direction_degrees: Option<f32>,
+    pub wave_direction_compass: Option<&'static str>,
+}
+
+/// The latest sea level measured by a mareograph, in millimetres.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeaLevelData {
+    pub time_of_observation: DateTime<Utc>,
+    /// Relative to the N2000 height system
+    pub sea_level_n2000_millimeters: Option<Reading<f64>>,
+    /// Relative to the theoretical mean water level of the mareograph
+    pub sea_level_relative_to_mean_millimeters: Option<Reading<f64>>,
+}
+
+/// One time step of a sea level forecast, in millimetres.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeaLevelForecastStep {
+    pub time: DateTime<Utc>,
+    pub sea_level_n2000_millimeters: Option<f64>,
+    pub sea_level_relative_to_mean_millimeters: Option<f64>,
+}
+
+/// Lightning strikes located within `radius_kilometers` of a station during
+/// the last hour.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LightningActivity {
+    pub radius_kilometers: f64,
+    pub nearby: bool,
+    pub strikes_last_30_minutes: usize,
+    pub strikes_last_60_minutes: usize,
+    pub nearest_strike_kilometers: Option<f64>,
+    pub latest_strike: Option<DateTime<Utc>>,
+}
+
+/// Aggregates of one day in Helsinki time.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    /// Only available once the day is over
+    pub min_temperature_celsius: Option<f64>,
+    /// Only available once the day is over
+    pub max_temperature_celsius: Option<f64>,
+    /// Highest of the hourly maximum gusts
+    pub max_gust_speed_meters_per_second: Option<f64>,
+    /// Mean of the hourly mean wind speeds
+    pub mean_wind_speed_meters_per_second: Option<f64>,
+}
+
+/// Aggregates of the hour ending at `time`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HourlyWind {
+    pub time: DateTime<Utc>,
+    pub mean_wind_speed_meters_per_second: Option<f64>,
+    pub max_gust_speed_meters_per_second: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklySummary {
+    /// Ordered by date, newest last
+    pub days: Vec<DailySummary>,
+    pub hourly_wind: Vec<HourlyWind>,
+}
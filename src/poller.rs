@@ -0,0 +1,44 @@
+use chrono::{Duration, Utc};
+use rocket::fairing::AdHoc;
+use std::sync::Arc;
+
+use crate::fmi_api::cache::next_publication_time;
+use crate::fmi_api::provider::{FmiProvider, HOURLY_CACHE_MINUTES};
+
+/// Starts tasks on liftoff that refresh the observations of every station
+/// whenever FMI should have published new ones, and the forecasts once an
+/// hour, so page loads find them in the caches instead of waiting on FMI.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Weather poller", |rocket| {
+        Box::pin(async move {
+            match rocket.state::<Arc<FmiProvider>>().cloned() {
+                Some(provider) => {
+                    tokio::spawn(poll_observations(provider.clone()));
+                    tokio::spawn(poll_forecasts(provider));
+                }
+                None => println!("Weather poller not started: missing managed state"),
+            }
+        })
+    })
+}
+
+async fn poll_observations(provider: Arc<FmiProvider>) {
+    loop {
+        provider.refresh_observations().await;
+
+        let wait = (next_publication_time(Utc::now()) - Utc::now())
+            .to_std()
+            .unwrap_or_default();
+        tokio::time::sleep(wait).await;
+    }
+}
+
+async fn poll_forecasts(provider: Arc<FmiProvider>) {
+    let interval = Duration::minutes(HOURLY_CACHE_MINUTES)
+        .to_std()
+        .unwrap_or_default();
+    loop {
+        provider.refresh_forecasts().await;
+        tokio::time::sleep(interval).await;
+    }
+}
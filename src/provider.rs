@@ -0,0 +1,128 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use std::fmt;
+
+use crate::model::{
+    ForecastStep, LightningActivity, ObservationSeries, SeaLevelData, SeaLevelForecastStep,
+    SeaStateData, TimeRange, WaveForecastStep, WeatherData, WeeklySummary,
+};
+
+#[cfg(test)]
+pub mod fixture;
+
+/// What the routes need to know about a station, whatever its data source.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StationMetadata {
+    pub id: String,
+    pub display_name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// From the data source's own station catalogue, when it has one
+    pub official_name: Option<String>,
+    pub swedish_name: Option<String>,
+    pub station_types: Vec<String>,
+    pub active: Option<bool>,
+    /// The wave buoy whose sea state is shown for the station
+    pub wave_buoy: Option<String>,
+    /// The mareograph whose sea level is shown for the station
+    pub mareograph: Option<String>,
+}
+
+/// Why a provider could not return data, whatever its source. Providers map
+/// their own errors into these.
+#[derive(Debug, Clone)]
+pub enum ProviderError {
+    /// The data source did not respond in time.
+    Timeout,
+    /// The request to the data source could not be completed.
+    Network(String),
+    /// The data source responded with a non-success status code.
+    HttpStatus(u16),
+    /// The response could not be parsed.
+    InvalidResponse(String),
+    /// The data source rejected the query.
+    Rejected { code: String, text: String },
+    /// The data source has no recent data for the station.
+    NoData,
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::Timeout => write!(f, "data source did not respond in time"),
+            ProviderError::Network(message) => write!(f, "request failed: {}", message),
+            ProviderError::HttpStatus(status) => {
+                write!(f, "data source responded with status {}", status)
+            }
+            ProviderError::InvalidResponse(message) => write!(f, "invalid response: {}", message),
+            ProviderError::Rejected { code, text } => {
+                write!(f, "data source rejected the query {}: {}", code, text)
+            }
+            ProviderError::NoData => write!(f, "no observations in response"),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+/// A source of station observations, marine data and forecasts. Stations are
+/// identified by the id used in the page URL, e.g. `harmaja`. The marine and
+/// forecast fetches return `None` for stations the provider has no such data
+/// for, e.g. a station without a nearby wave buoy.
+#[async_trait]
+pub trait WeatherProvider: Send + Sync {
+    /// Every station of the provider, in no particular order.
+    fn stations(&self) -> Vec<StationMetadata>;
+
+    fn station(&self, station_id: &str) -> Option<StationMetadata>;
+
+    /// The newest observations of a station.
+    async fn fetch_latest(&self, station_id: &str) -> Result<WeatherData, ProviderError>;
+
+    /// Every observation of a station during `time_range`.
+    async fn fetch_series(
+        &self,
+        station_id: &str,
+        time_range: TimeRange,
+    ) -> Result<ObservationSeries, ProviderError>;
+
+    /// The latest sea state at the station's wave buoy.
+    async fn fetch_sea_state(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<SeaStateData>, ProviderError>;
+
+    /// The latest sea level at the station's mareograph.
+    async fn fetch_sea_level(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<SeaLevelData>, ProviderError>;
+
+    async fn fetch_forecast(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<Vec<ForecastStep>>, ProviderError>;
+
+    async fn fetch_sea_level_forecast(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<Vec<SeaLevelForecastStep>>, ProviderError>;
+
+    async fn fetch_wave_forecast(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<Vec<WaveForecastStep>>, ProviderError>;
+
+    /// Lightning strikes near the station during the last hour.
+    async fn fetch_lightning(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<LightningActivity>, ProviderError>;
+
+    /// Daily and hourly aggregates of the last days.
+    async fn fetch_weekly_summary(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<WeeklySummary>, ProviderError>;
+}
@@ -0,0 +1,168 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::path::PathBuf;
+
+use super::{ProviderError, StationMetadata, WeatherProvider};
+use crate::fmi_api::forecast::parse_forecast;
+use crate::fmi_api::history::parse_weekly_summary;
+use crate::fmi_api::lightning::parse_lightning_activity;
+use crate::fmi_api::mareograph::{parse_latest_sea_level, parse_sea_level_forecast};
+use crate::fmi_api::wave::{parse_latest_sea_state, parse_wave_forecast};
+use crate::fmi_api::{
+    parse_latest_weather_data, parse_observation_series, FmiError, ResponseFormat,
+};
+use crate::model::{
+    ForecastStep, LightningActivity, ObservationSeries, SeaLevelData, SeaLevelForecastStep,
+    SeaStateData, TimeRange, WaveForecastStep, WeatherData, WeeklySummary,
+};
+
+/// Radius of the lightning counts, as in the default configuration.
+const LIGHTNING_RADIUS_KILOMETERS: f64 = 20.0;
+
+/// Serves saved FMI responses instead of asking FMI, for testing routes.
+/// `<directory>/<station>/<source>.xml` holds the `simple` response of one
+/// source, e.g. `harmaja/latest.xml`. A missing file means the station has
+/// no such data. The series is served whatever the time range asked for.
+pub struct FixtureProvider {
+    directory: PathBuf,
+    stations: Vec<StationMetadata>,
+}
+
+impl FixtureProvider {
+    pub fn new(directory: impl Into<PathBuf>, stations: Vec<StationMetadata>) -> FixtureProvider {
+        FixtureProvider {
+            directory: directory.into(),
+            stations,
+        }
+    }
+
+    fn read(&self, station_id: &str, source: &str) -> Option<String> {
+        let path = self
+            .directory
+            .join(station_id)
+            .join(format!("{}.xml", source));
+        std::fs::read_to_string(path).ok()
+    }
+
+    fn parse<T>(
+        &self,
+        station_id: &str,
+        source: &str,
+        parse: impl FnOnce(&str) -> Result<T, FmiError>,
+    ) -> Result<Option<T>, ProviderError> {
+        self.read(station_id, source)
+            .map(|xml| parse(&xml))
+            .transpose()
+            .map_err(ProviderError::from)
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for FixtureProvider {
+    fn stations(&self) -> Vec<StationMetadata> {
+        self.stations.clone()
+    }
+
+    fn station(&self, station_id: &str) -> Option<StationMetadata> {
+        self.stations
+            .iter()
+            .find(|station| station.id == station_id)
+            .cloned()
+    }
+
+    async fn fetch_latest(&self, station_id: &str) -> Result<WeatherData, ProviderError> {
+        self.parse(station_id, "latest", |xml| {
+            parse_latest_weather_data(&[xml.to_string()], ResponseFormat::Simple)
+        })?
+        .ok_or(ProviderError::NoData)
+    }
+
+    async fn fetch_series(
+        &self,
+        station_id: &str,
+        _time_range: TimeRange,
+    ) -> Result<ObservationSeries, ProviderError> {
+        self.parse(station_id, "series", |xml| {
+            parse_observation_series(&[xml.to_string()], ResponseFormat::Simple)
+        })?
+        .ok_or(ProviderError::NoData)
+    }
+
+    async fn fetch_sea_state(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<SeaStateData>, ProviderError> {
+        self.parse(station_id, "sea_state", |xml| {
+            parse_latest_sea_state(xml, ResponseFormat::Simple)
+        })
+    }
+
+    async fn fetch_sea_level(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<SeaLevelData>, ProviderError> {
+        self.parse(station_id, "sea_level", |xml| {
+            parse_latest_sea_level(xml, ResponseFormat::Simple)
+        })
+    }
+
+    async fn fetch_forecast(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<Vec<ForecastStep>>, ProviderError> {
+        self.parse(station_id, "forecast", |xml| {
+            parse_forecast(xml, ResponseFormat::Simple)
+        })
+    }
+
+    async fn fetch_sea_level_forecast(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<Vec<SeaLevelForecastStep>>, ProviderError> {
+        self.parse(station_id, "sea_level_forecast", |xml| {
+            parse_sea_level_forecast(xml, ResponseFormat::Simple)
+        })
+    }
+
+    async fn fetch_wave_forecast(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<Vec<WaveForecastStep>>, ProviderError> {
+        self.parse(station_id, "wave_forecast", |xml| {
+            parse_wave_forecast(xml, ResponseFormat::Simple)
+        })
+    }
+
+    async fn fetch_lightning(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<LightningActivity>, ProviderError> {
+        let Some(station) = self.station(station_id) else {
+            return Err(ProviderError::NoData);
+        };
+        self.parse(station_id, "lightning", |xml| {
+            parse_lightning_activity(
+                xml,
+                ResponseFormat::Simple,
+                (station.latitude, station.longitude),
+                LIGHTNING_RADIUS_KILOMETERS,
+                Utc::now(),
+            )
+        })
+    }
+
+    async fn fetch_weekly_summary(
+        &self,
+        station_id: &str,
+    ) -> Result<Option<WeeklySummary>, ProviderError> {
+        let (Some(daily), Some(hourly)) = (
+            self.read(station_id, "daily"),
+            self.read(station_id, "hourly"),
+        ) else {
+            return Ok(None);
+        };
+        parse_weekly_summary(&[daily], &[hourly])
+            .map(Some)
+            .map_err(ProviderError::from)
+    }
+}
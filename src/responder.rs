@@ -1,4 +1,11 @@
-use crate::fmi_api::WeatherData;
+use crate::daylight::Daylight;
+use crate::freshness::Freshness;
+use crate::model::{
+    ForecastStep, LightningActivity, SeaLevelData, SeaLevelForecastStep, SeaStateData,
+    WaveForecastStep, WeatherData, WeeklySummary,
+};
+use crate::provider::{ProviderError, StationMetadata};
+use crate::trends::Trends;
 use rocket::response::{Responder, Result};
 use rocket::{
     http::Status,
@@ -6,12 +13,33 @@ use rocket::{
     Request,
 };
 use rocket_dyn_templates::Template;
+use serde::Serialize;
+
+/// Everything known about a station, as returned to JSON clients.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StationReport {
+    pub station: StationMetadata,
+    #[serde(flatten)]
+    pub weather: WeatherData,
+    #[serde(flatten)]
+    pub freshness: Freshness,
+    pub sea_state: Option<SeaStateData>,
+    pub sea_level: Option<SeaLevelData>,
+    pub forecast: Option<Vec<ForecastStep>>,
+    pub sea_level_forecast: Option<Vec<SeaLevelForecastStep>>,
+    pub wave_forecast: Option<Vec<WaveForecastStep>>,
+    pub lightning: Option<LightningActivity>,
+    pub weekly_summary: Option<WeeklySummary>,
+    pub trends: Option<Trends>,
+    pub daylight: Daylight,
+}
 
 #[derive(Debug)]
 pub enum WeatherResponse {
-    Template(Template, WeatherData),
+    Template(Template, Box<StationReport>),
     NotFound(String),
-    InternalServerError(String),
+    ProviderError(ProviderError),
 }
 
 impl<'r> Responder<'r, 'static> for WeatherResponse {
@@ -19,33 +47,63 @@ impl<'r> Responder<'r, 'static> for WeatherResponse {
         let accept_json = req
             .headers()
             .get_one("Accept")
-            .map_or(false, |accept| accept == "application/json");
+            .is_some_and(|accept| accept == "application/json");
 
         match self {
-            WeatherResponse::Template(template, weather_data) => {
+            WeatherResponse::Template(template, report) => {
                 if accept_json {
-                    let json = rocket::serde::json::Json(weather_data);
+                    let json = rocket::serde::json::Json(report);
                     json.respond_to(req)
                 } else {
                     template.respond_to(req)
                 }
             }
             WeatherResponse::NotFound(message) => {
-                if accept_json {
-                    let json = content::RawJson(format!("{{ \"error\": \"{}\" }}", message));
-                    status::Custom(Status::NotFound, json).respond_to(req)
-                } else {
-                    status::Custom(Status::NotFound, message).respond_to(req)
-                }
+                error_response(Status::NotFound, message, accept_json, req)
             }
-            WeatherResponse::InternalServerError(message) => {
-                if accept_json {
-                    let json = content::RawJson(format!("{{ \"error\": \"{}\" }}", message));
-                    status::Custom(Status::InternalServerError, json).respond_to(req)
-                } else {
-                    status::Custom(Status::InternalServerError, message).respond_to(req)
-                }
+            WeatherResponse::ProviderError(error) => {
+                let (status, message) = match error {
+                    ProviderError::Timeout => (
+                        Status::GatewayTimeout,
+                        "Weather service did not respond in time.".to_string(),
+                    ),
+                    ProviderError::Network(_) => (
+                        Status::BadGateway,
+                        "Failed to fetch weather data.".to_string(),
+                    ),
+                    ProviderError::HttpStatus(status) => (
+                        Status::BadGateway,
+                        format!("Weather service responded with status {}.", status),
+                    ),
+                    ProviderError::InvalidResponse(_) => (
+                        Status::BadGateway,
+                        "Failed to parse weather data.".to_string(),
+                    ),
+                    ProviderError::Rejected { code, text } => (
+                        Status::BadGateway,
+                        format!("Weather service rejected the query ({}): {}", code, text),
+                    ),
+                    ProviderError::NoData => (
+                        Status::ServiceUnavailable,
+                        "No recent observations for this station.".to_string(),
+                    ),
+                };
+                error_response(status, message, accept_json, req)
             }
         }
     }
 }
+
+fn error_response(
+    status: Status,
+    message: String,
+    accept_json: bool,
+    req: &Request<'_>,
+) -> Result<'static> {
+    if accept_json {
+        let json = content::RawJson(serde_json::json!({ "error": message }).to_string());
+        status::Custom(status, json).respond_to(req)
+    } else {
+        status::Custom(status, message).respond_to(req)
+    }
+}
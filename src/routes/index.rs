@@ -1,8 +1,8 @@
-use crate::stations::stations::WeatherLocation;
+use crate::provider::WeatherProvider;
 use rocket::State;
 use rocket_dyn_templates::Template;
 use serde::Serialize;
-use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Serialize)]
 struct Entry {
@@ -16,12 +16,12 @@ struct TemplateContext {
 }
 
 #[get("/")]
-pub fn home_page(urls: &State<HashMap<String, WeatherLocation>>) -> Template {
+pub fn home_page(provider: &State<Arc<dyn WeatherProvider>>) -> Template {
     let mut entries = Vec::new();
-    for (key, value) in urls.iter() {
+    for station in provider.stations() {
         entries.push(Entry {
-            key: key.clone(),
-            name: value.display_name.clone(),
+            key: station.id,
+            name: station.display_name,
         });
     }
 
@@ -0,0 +1,9 @@
+use crate::fmi_api::client::{RequestMetrics, RequestMetricsSnapshot};
+use rocket::serde::json::Json;
+use rocket::State;
+use std::sync::Arc;
+
+#[get("/metrics")]
+pub fn get_metrics(metrics: &State<Arc<RequestMetrics>>) -> Json<RequestMetricsSnapshot> {
+    Json(metrics.snapshot())
+}
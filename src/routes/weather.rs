@@ -1,61 +1,602 @@
-use crate::fmi_api::{fetch_xml, parse_latest_weather_data};
-use crate::responder::WeatherResponse;
-use crate::WeatherLocation;
-use reqwest_middleware::ClientWithMiddleware;
+use crate::config::Config;
+use crate::daylight::{compute_daylight, Daylight};
+use crate::fmi_api::cache::SingleFlightCache;
+use crate::freshness::check_freshness;
+use crate::model::{
+    DailySummary, ForecastStep, LightningActivity, Reading, SeaLevelData, SeaLevelForecastStep,
+    SeaStateData, TimeRange, WaveForecastStep,
+};
+use crate::provider::{ProviderError, WeatherProvider};
+use crate::responder::{StationReport, WeatherResponse};
+use crate::trends::{compute_trends, Shift, Tendency, Trend, Trends, TREND_HOURS};
+use chrono::{DateTime, Duration, DurationRound, FixedOffset, Utc};
+use chrono_tz::Europe::Helsinki;
+use futures::join;
 use rocket::State;
 use rocket_dyn_templates::Template;
-use std::collections::HashMap;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// The forecast tables show every hour of the next `HOURLY_FORECAST_HOURS`
+/// and every third hour after that until `SHOWN_FORECAST_HOURS`, to keep the
+/// page light. The JSON has every step.
+const HOURLY_FORECAST_HOURS: i64 = 12;
+const SHOWN_FORECAST_HOURS: i64 = 24;
+
+#[derive(Serialize)]
+struct TemplateValue {
+    value: String,
+    /// Only set when it differs from the time of the record
+    time: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TemplateContext {
+    name: String,
+    time: String,
+    /// Set when the observation is older than the stale threshold
+    stale_warning: Option<String>,
+    wind_speed: Option<TemplateValue>,
+    temperature: Option<TemplateValue>,
+    wind_direction: Option<TemplateValue>,
+    wind_gust_speed: Option<TemplateValue>,
+    pressure: Option<TemplateValue>,
+    wind_speed_trend: Option<String>,
+    wind_direction_trend: Option<String>,
+    wind_gust_speed_trend: Option<String>,
+    pressure_trend: Option<String>,
+    humidity: Option<TemplateValue>,
+    dew_point: Option<TemplateValue>,
+    visibility: Option<TemplateValue>,
+    cloud_cover: Option<TemplateValue>,
+    precipitation: Option<TemplateValue>,
+    sea_state: Option<SeaStateContext>,
+    sea_level: Option<SeaLevelContext>,
+    forecast: Option<Vec<ForecastContext>>,
+    sea_level_forecast: Option<Vec<SeaLevelForecastContext>>,
+    wave_forecast: Option<Vec<WaveForecastContext>>,
+    lightning: Option<LightningContext>,
+    last_days: Option<Vec<DailySummaryContext>>,
+    daylight: DaylightContext,
+}
+
+#[derive(Serialize)]
+struct SeaStateContext {
+    buoy: String,
+    time: String,
+    wave_height: Option<TemplateValue>,
+    wave_period: Option<TemplateValue>,
+    wave_direction: Option<TemplateValue>,
+    water_temperature: Option<TemplateValue>,
+}
+
+#[derive(Serialize)]
+struct SeaLevelContext {
+    mareograph: String,
+    time: String,
+    /// Centimetres relative to the theoretical mean water level, with sign
+    relative_to_mean: Option<TemplateValue>,
+    /// Centimetres in the N2000 height system, with sign
+    n2000: Option<TemplateValue>,
+}
+
+#[derive(Serialize)]
+struct ForecastContext {
+    time: String,
+    temperature: Option<String>,
+    wind_speed: Option<String>,
+    wind_gust_speed: Option<String>,
+    wind_direction: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct SeaLevelForecastContext {
+    time: String,
+    /// Centimetres relative to the theoretical mean water level, with sign
+    relative_to_mean: Option<String>,
+}
+
+#[derive(Serialize)]
+struct WaveForecastContext {
+    time: String,
+    wave_height: Option<String>,
+    wave_period: Option<String>,
+    wave_direction: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct DaylightContext {
+    sunrise: String,
+    sunset: String,
+    civil_dawn: String,
+    civil_dusk: String,
+    nautical_dawn: String,
+    nautical_dusk: String,
+}
+
+#[derive(Serialize)]
+struct LightningContext {
+    nearby: bool,
+    radius: String,
+    strikes_last_30_minutes: usize,
+    strikes_last_60_minutes: usize,
+    nearest: Option<String>,
+    latest: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DailySummaryContext {
+    date: String,
+    min_temperature: Option<String>,
+    max_temperature: Option<String>,
+    max_gust_speed: Option<String>,
+    mean_wind_speed: Option<String>,
+}
 
 #[get("/<name>")]
 pub async fn get_weather(
-    urls: &State<HashMap<String, WeatherLocation>>,
-    reqwest_client: &State<ClientWithMiddleware>,
+    config: &State<Config>,
+    provider: &State<Arc<dyn WeatherProvider>>,
+    trend_cache: &State<SingleFlightCache<Trends, ProviderError>>,
     name: &str,
 ) -> WeatherResponse {
-    let selected_station = match urls.get(name) {
-        Some(location) => location,
+    let station = match provider.station(name) {
+        Some(station) => station,
         None => {
             return WeatherResponse::NotFound("Resource not found.".to_string());
         }
     };
-    let selected_station_url = selected_station.url.as_str();
-    let xml = match fetch_xml((name, selected_station_url), reqwest_client).await {
-        Ok(xml) => xml,
-        Err(e) => {
-            println!("Failed to fetch XML: {}", e);
-            return WeatherResponse::InternalServerError(
-                "Failed to fetch weather data.".to_string(),
-            );
-        }
+    let trends = async {
+        trend_cache
+            .get_or_fetch(name, || async {
+                let time_range =
+                    TimeRange::last(Duration::hours(TREND_HOURS), Duration::minutes(10));
+                let series = provider.fetch_series(name, time_range).await?;
+                Ok(compute_trends(&series))
+            })
+            .await
+            .map_err(|e| println!("Failed to compute trends: {}", e))
+            .ok()
     };
-
-    let weather_data = match parse_latest_weather_data(xml.as_ref()) {
-        Some(data) => data,
-        None => {
-            println!("Failed to parse weather data");
-            return WeatherResponse::InternalServerError(
-                "Failed to parse weather data.".to_string(),
-            );
+    let (
+        weather_data,
+        sea_state,
+        sea_level,
+        forecast,
+        sea_level_forecast,
+        wave_forecast,
+        lightning,
+        weekly_summary,
+        trends,
+    ) = join!(
+        provider.fetch_latest(name),
+        provider.fetch_sea_state(name),
+        provider.fetch_sea_level(name),
+        provider.fetch_forecast(name),
+        provider.fetch_sea_level_forecast(name),
+        provider.fetch_wave_forecast(name),
+        provider.fetch_lightning(name),
+        provider.fetch_weekly_summary(name),
+        trends
+    );
+    let sea_state = optional("sea state", sea_state);
+    let sea_level = optional("sea level", sea_level);
+    let forecast = optional("forecast", forecast);
+    let sea_level_forecast = optional("sea level forecast", sea_level_forecast);
+    let wave_forecast = optional("wave forecast", wave_forecast);
+    let lightning = optional("lightning", lightning);
+    let weekly_summary = optional("weekly summary", weekly_summary);
+    let weather_data = match weather_data {
+        Ok(data) => data,
+        Err(e) => {
+            println!("Failed to fetch weather data: {}", e);
+            return WeatherResponse::ProviderError(e);
         }
     };
 
     println!("{:?}", weather_data);
 
-    let context = [
-        ("time", &weather_data.time_of_observation),
-        ("wind_speed", &weather_data.wind_speed_meters_per_second),
-        ("temperature", &weather_data.temperature_celsius),
-        ("wind_direction", &weather_data.wind_direction),
-        (
-            "wind_gust_speed",
+    let now = Utc::now();
+    let daylight = compute_daylight(station.latitude, station.longitude, now);
+    let record_time = weather_data.time_of_observation;
+    let freshness = check_freshness(record_time, now, config.stale_after_minutes);
+    let context = TemplateContext {
+        name: station.display_name.clone(),
+        time: format_time(record_time),
+        stale_warning: freshness
+            .stale
+            .then(|| format!("Data is {} minutes old.", freshness.age_seconds / 60)),
+        wind_speed: template_value(&weather_data.wind_speed_meters_per_second, record_time),
+        temperature: template_value(&weather_data.temperature_celsius, record_time),
+        wind_direction: template_value(
+            &weather_data
+                .wind_direction_degrees
+                .zip(weather_data.wind_direction_compass)
+                .map(|(reading, compass)| Reading {
+                    value: compass,
+                    time_of_observation: reading.time_of_observation,
+                }),
+            record_time,
+        ),
+        wind_gust_speed: template_value(
             &weather_data.wind_gust_speed_meters_per_second,
+            record_time,
+        ),
+        pressure: template_value(&weather_data.pressure_hectopascals, record_time),
+        wind_speed_trend: trends
+            .as_ref()
+            .and_then(|trends| trends.wind_speed.as_ref())
+            .map(|trend| format_trend(trend, "m/s")),
+        wind_direction_trend: trends
+            .as_ref()
+            .and_then(|trends| trends.wind_direction.as_ref())
+            .map(|trend| {
+                let arrow = match trend.shift {
+                    Shift::Veering => "↻ veering",
+                    Shift::Steady => "→",
+                    Shift::Backing => "↺ backing",
+                };
+                format!(
+                    "{} {:.0}° in {} h",
+                    arrow,
+                    trend.change_degrees.abs(),
+                    trend.hours
+                )
+            }),
+        wind_gust_speed_trend: trends
+            .as_ref()
+            .and_then(|trends| trends.wind_gust_speed.as_ref())
+            .map(|trend| format_trend(trend, "m/s")),
+        pressure_trend: trends
+            .as_ref()
+            .and_then(|trends| trends.pressure.as_ref())
+            .map(|trend| format_trend(trend, "hPa")),
+        humidity: template_value(&weather_data.humidity_percent, record_time),
+        dew_point: template_value(&weather_data.dew_point_celsius, record_time),
+        visibility: template_value(
+            &weather_data.visibility_meters.map(|reading| Reading {
+                value: format!("{:.1}", reading.value / 1000.0),
+                time_of_observation: reading.time_of_observation,
+            }),
+            record_time,
+        ),
+        cloud_cover: template_value(
+            &weather_data.total_cloud_cover_oktas.map(|reading| Reading {
+                value: format_cloud_cover(reading.value),
+                time_of_observation: reading.time_of_observation,
+            }),
+            record_time,
+        ),
+        precipitation: template_value(
+            &weather_data.precipitation_one_hour_millimeters,
+            record_time,
         ),
-        ("name", &selected_station.display_name),
-    ]
-    .iter()
-    .filter(|(_, value)| value != &"NaN")
-    .cloned()
-    .collect::<HashMap<_, _>>();
+        sea_state: station
+            .wave_buoy
+            .as_deref()
+            .zip(sea_state.as_ref())
+            .map(|(buoy, sea_state)| sea_state_context(buoy, sea_state)),
+        sea_level: station
+            .mareograph
+            .as_deref()
+            .zip(sea_level.as_ref())
+            .map(|(mareograph, sea_level)| sea_level_context(mareograph, sea_level)),
+        forecast: forecast.as_ref().map(|steps| {
+            steps
+                .iter()
+                .filter(|step| is_shown_forecast_step(step.time, now))
+                .map(forecast_context)
+                .collect()
+        }),
+        sea_level_forecast: sea_level_forecast.as_ref().map(|steps| {
+            steps
+                .iter()
+                .filter(|step| is_shown_forecast_step(step.time, now))
+                .map(sea_level_forecast_context)
+                .collect()
+        }),
+        wave_forecast: wave_forecast.as_ref().map(|steps| {
+            steps
+                .iter()
+                .filter(|step| is_shown_forecast_step(step.time, now))
+                .map(wave_forecast_context)
+                .collect()
+        }),
+        lightning: lightning.as_ref().map(lightning_context),
+        last_days: weekly_summary
+            .as_ref()
+            .map(|summary| summary.days.iter().map(daily_summary_context).collect()),
+        daylight: daylight_context(&daylight),
+    };
+
+    WeatherResponse::Template(
+        Template::render("weather", &context),
+        Box::new(StationReport {
+            station,
+            weather: weather_data,
+            freshness,
+            sea_state,
+            sea_level,
+            forecast,
+            sea_level_forecast,
+            wave_forecast,
+            lightning,
+            weekly_summary,
+            trends,
+            daylight,
+        }),
+    )
+}
+
+/// Data that only makes the page less complete when it cannot be fetched.
+fn optional<T>(what: &str, result: Result<Option<T>, ProviderError>) -> Option<T> {
+    result
+        .map_err(|e| println!("Failed to fetch {}: {}", what, e))
+        .ok()
+        .flatten()
+}
+
+fn sea_state_context(buoy: &str, sea_state: &SeaStateData) -> SeaStateContext {
+    let record_time = sea_state.time_of_observation;
+    SeaStateContext {
+        buoy: buoy.to_string(),
+        time: format_time(record_time),
+        wave_height: template_value(&sea_state.significant_wave_height_meters, record_time),
+        wave_period: template_value(&sea_state.wave_period_seconds, record_time),
+        wave_direction: template_value(
+            &sea_state
+                .wave_direction_degrees
+                .zip(sea_state.wave_direction_compass)
+                .map(|(reading, compass)| Reading {
+                    value: compass,
+                    time_of_observation: reading.time_of_observation,
+                }),
+            record_time,
+        ),
+        water_temperature: template_value(&sea_state.water_temperature_celsius, record_time),
+    }
+}
+
+fn sea_level_context(mareograph: &str, sea_level: &SeaLevelData) -> SeaLevelContext {
+    let record_time = sea_level.time_of_observation;
+    let centimeters = |reading: &Option<Reading<f64>>| {
+        reading.map(|reading| Reading {
+            value: format!("{:+.0}", reading.value / 10.0),
+            time_of_observation: reading.time_of_observation,
+        })
+    };
+    SeaLevelContext {
+        mareograph: mareograph.to_string(),
+        time: format_time(record_time),
+        relative_to_mean: template_value(
+            &centimeters(&sea_level.sea_level_relative_to_mean_millimeters),
+            record_time,
+        ),
+        n2000: template_value(
+            &centimeters(&sea_level.sea_level_n2000_millimeters),
+            record_time,
+        ),
+    }
+}
+
+fn forecast_context(step: &ForecastStep) -> ForecastContext {
+    let one_decimal = |value: Option<f64>| value.map(|value| format!("{:.1}", value));
+    ForecastContext {
+        time: format_hour(step.time),
+        temperature: one_decimal(step.temperature_celsius),
+        wind_speed: one_decimal(step.wind_speed_meters_per_second),
+        wind_gust_speed: one_decimal(step.wind_gust_speed_meters_per_second),
+        wind_direction: step.wind_direction_compass,
+    }
+}
+
+fn sea_level_forecast_context(step: &SeaLevelForecastStep) -> SeaLevelForecastContext {
+    SeaLevelForecastContext {
+        time: format_hour(step.time),
+        relative_to_mean: step
+            .sea_level_relative_to_mean_millimeters
+            .map(|millimeters| format!("{:+.0}", millimeters / 10.0)),
+    }
+}
+
+fn wave_forecast_context(step: &WaveForecastStep) -> WaveForecastContext {
+    WaveForecastContext {
+        time: format_hour(step.time),
+        wave_height: step
+            .significant_wave_height_meters
+            .map(|meters| format!("{:.1}", meters)),
+        wave_period: step
+            .wave_period_seconds
+            .map(|seconds| format!("{:.0}", seconds)),
+        wave_direction: step.wave_direction_compass,
+    }
+}
+
+fn daylight_context(daylight: &Daylight) -> DaylightContext {
+    DaylightContext {
+        sunrise: format_clock(daylight.sunrise),
+        sunset: format_clock(daylight.sunset),
+        civil_dawn: format_clock(daylight.civil_dawn),
+        civil_dusk: format_clock(daylight.civil_dusk),
+        nautical_dawn: format_clock(daylight.nautical_dawn),
+        nautical_dusk: format_clock(daylight.nautical_dusk),
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Helsinki)
+        .format("%d/%m/%Y %T")
+        .to_string()
+}
+
+/// Steps from the current hour on, every hour at first and every three
+/// hours after that. Cached forecasts also hold steps that have passed.
+fn is_shown_forecast_step(time: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    let current_hour = now.duration_trunc(Duration::hours(1)).unwrap_or(now);
+    let ahead = time - now;
+    time >= current_hour
+        && (ahead <= Duration::hours(HOURLY_FORECAST_HOURS)
+            || (ahead <= Duration::hours(SHOWN_FORECAST_HOURS)
+                && time.duration_trunc(Duration::hours(3)) == Ok(time)))
+}
+
+/// A time of the day, or a dash when the sun does not reach the angle.
+fn format_clock(time: Option<DateTime<FixedOffset>>) -> String {
+    time.map_or_else(|| "–".to_string(), |time| time.format("%H:%M").to_string())
+}
+
+fn lightning_context(lightning: &LightningActivity) -> LightningContext {
+    LightningContext {
+        nearby: lightning.nearby,
+        radius: format!("{:.0}", lightning.radius_kilometers),
+        strikes_last_30_minutes: lightning.strikes_last_30_minutes,
+        strikes_last_60_minutes: lightning.strikes_last_60_minutes,
+        nearest: lightning
+            .nearest_strike_kilometers
+            .map(|kilometers| format!("{:.0}", kilometers)),
+        latest: lightning.latest_strike.map(format_time),
+    }
+}
+
+fn daily_summary_context(day: &DailySummary) -> DailySummaryContext {
+    let one_decimal = |value: Option<f64>| value.map(|value| format!("{:.1}", value));
+    DailySummaryContext {
+        date: day.date.format("%d/%m").to_string(),
+        min_temperature: one_decimal(day.min_temperature_celsius),
+        max_temperature: one_decimal(day.max_temperature_celsius),
+        max_gust_speed: one_decimal(day.max_gust_speed_meters_per_second),
+        mean_wind_speed: one_decimal(day.mean_wind_speed_meters_per_second),
+    }
+}
+
+fn format_trend(trend: &Trend, unit: &str) -> String {
+    let arrow = match trend.tendency {
+        Tendency::Rising => "↑",
+        Tendency::Steady => "→",
+        Tendency::Falling => "↓",
+    };
+    format!(
+        "{} {:+.1} {} in {} h",
+        arrow, trend.change, unit, trend.hours
+    )
+}
+
+fn format_hour(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Helsinki)
+        .format("%d/%m %H:%M")
+        .to_string()
+}
+
+/// Oktas out of eight. FMI reports a sky hidden by fog or snow as 9.
+fn format_cloud_cover(oktas: f64) -> String {
+    if oktas > 8.0 {
+        "obscured".to_string()
+    } else {
+        format!("{}/8", oktas)
+    }
+}
+
+fn template_value<T: ToString>(
+    reading: &Option<Reading<T>>,
+    record_time: DateTime<Utc>,
+) -> Option<TemplateValue> {
+    reading.as_ref().map(|reading| TemplateValue {
+        value: reading.value.to_string(),
+        time: Some(reading.time_of_observation)
+            .filter(|time| *time != record_time)
+            .map(format_time),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::fixture::FixtureProvider;
+    use crate::provider::StationMetadata;
+    use chrono::TimeZone;
+    use rocket::http::{Accept, Status};
+    use rocket::local::blocking::Client;
+
+    fn client() -> Client {
+        let harmaja = StationMetadata {
+            id: "harmaja".to_string(),
+            display_name: "Helsinki Harmaja".to_string(),
+            latitude: 60.10512,
+            longitude: 24.97539,
+            official_name: None,
+            swedish_name: None,
+            station_types: Vec::new(),
+            active: None,
+            wave_buoy: None,
+            mareograph: None,
+        };
+        let provider: Arc<dyn WeatherProvider> = Arc::new(FixtureProvider::new(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures"),
+            vec![harmaja],
+        ));
+        let rocket = rocket::build()
+            .manage(Config::default())
+            .manage(provider)
+            .manage(SingleFlightCache::<Trends, ProviderError>::default())
+            .mount("/", routes![get_weather])
+            .attach(Template::fairing());
+        Client::tracked(rocket).expect("valid rocket instance")
+    }
+
+    #[test]
+    fn renders_station_page_from_fixtures() {
+        let client = client();
+        let response = client.get("/harmaja").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let html = response.into_string().unwrap();
+        assert!(html.contains("<h1>Helsinki Harmaja</h1>"));
+        assert!(html.contains("Temperature: 14.5"));
+        // The gust of the newest observation is missing
+        assert!(html.contains("Gust speed: 8 m/s (01/06/2024 14:50:00)"));
+        // The fixture is from 2024
+        assert!(html.contains("class=\"warning\">Data is"));
+        assert!(!html.contains("Sea state"));
+    }
+
+    #[test]
+    fn returns_station_report_as_json() {
+        let client = client();
+        let response = client.get("/harmaja").header(Accept::JSON).dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let report: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(report["station"]["id"], "harmaja");
+        assert_eq!(report["temperatureCelsius"]["value"], 14.5);
+        assert_eq!(report["stale"], true);
+        assert!(report["seaState"].is_null());
+        assert!(report["weeklySummary"]["days"].is_array());
+        assert!(report["daylight"]["sunrise"].is_string());
+    }
+
+    #[test]
+    fn unknown_station_is_not_found() {
+        let client = client();
+        let response = client.get("/atlantis").dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn forecast_steps_start_at_the_current_hour() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 40, 0).unwrap();
+        let at = |hours: i64| {
+            Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap() + Duration::hours(hours)
+        };
 
-    WeatherResponse::Template(Template::render("weather", &context), weather_data)
+        assert!(!is_shown_forecast_step(at(-2), now));
+        assert!(!is_shown_forecast_step(at(-1), now));
+        assert!(is_shown_forecast_step(at(0), now));
+        assert!(is_shown_forecast_step(at(12), now));
+        // Every three hours after the first twelve
+        assert!(!is_shown_forecast_step(at(13), now));
+        assert!(is_shown_forecast_step(at(15), now));
+        assert!(is_shown_forecast_step(at(24), now));
+        assert!(!is_shown_forecast_step(at(27), now));
+    }
 }
@@ -1,290 +1,769 @@
-pub mod stations {
-    use std::collections::HashMap;
+use crate::config::Config;
+use crate::fmi_api::catalogue::CatalogueStation;
+use crate::fmi_api::ResponseFormat;
+use std::collections::HashMap;
 
-    pub struct WeatherLocation {
-        pub display_name: String,
-        pub url: String,
-    }
+#[derive(Clone)]
+pub struct WeatherLocation {
+    pub display_name: String,
+    pub url: String,
+    pub forecast_url: String,
+    /// Not set for lake stations, which the wave model does not cover
+    pub wave_forecast_url: Option<String>,
+    /// Covers a box around the station, large enough to hold the
+    /// configured lightning radius
+    pub lightning_url: String,
+    /// Daily aggregates, only available as `simple`
+    pub daily_url: String,
+    /// Hourly aggregates, only available as `simple`
+    pub hourly_url: String,
+    pub format: ResponseFormat,
+    pub fmis_id: i32,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub wave_buoy: Option<MarineLocation>,
+    pub mareograph: Option<MarineLocation>,
+    /// Not set when the catalogue could not be loaded or does not know
+    /// the station
+    pub catalogue: Option<CatalogueStation>,
+}
 
-    struct WeatherStation {
-        id: &'static str,
-        display_name: &'static str,
-        fmis_id: i32,
-    }
-    const WEATHER_STATIONS: [WeatherStation; 50] = [
-        WeatherStation {
-            id: "rajakari",
-            display_name: "Turku Rajakari",
-            fmis_id: 100947,
-        },
-        WeatherStation {
-            id: "haapasaari",
-            display_name: "Kotka Haapasaari",
-            fmis_id: 101042,
-        },
-        WeatherStation {
-            id: "rankki",
-            display_name: "Kotka Rankki",
-            fmis_id: 101030,
-        },
-        WeatherStation {
-            id: "orrengrund",
-            display_name: "Loviisa Orrengrund",
-            fmis_id: 101039,
-        },
-        WeatherStation {
-            id: "kilpilahti_satama",
-            display_name: "Porvoo Kilpilahti satama",
-            fmis_id: 100683,
-        },
-        WeatherStation {
-            id: "emasalo",
-            display_name: "Porvoo Emäsalo",
-            fmis_id: 101023,
-        },
-        WeatherStation {
-            id: "kalbadagrund",
-            display_name: "Porvoo Kalbådagrund",
-            fmis_id: 101022,
-        },
-        WeatherStation {
-            id: "vuosaari_satama",
-            display_name: "Helsinki Vuosaari satama",
-            fmis_id: 151028,
-        },
-        WeatherStation {
-            id: "itatoukki",
-            display_name: "Sipoo Itätoukki",
-            fmis_id: 105392,
-        },
-        WeatherStation {
-            id: "harmaja",
-            display_name: "Helsinki Harmaja",
-            fmis_id: 100996,
-        },
-        WeatherStation {
-            id: "helsinki_majakka",
-            display_name: "Helsinki Helsingin Majakka",
-            fmis_id: 101003,
-        },
-        WeatherStation {
-            id: "makiluoto",
-            display_name: "Kirkkonummi Mäkiluoto",
-            fmis_id: 100997,
-        },
-        WeatherStation {
-            id: "bogaskar",
-            display_name: "Inkoo Bågaskär",
-            fmis_id: 100969,
-        },
-        WeatherStation {
-            id: "jussaro",
-            display_name: "Raasepori Jussarö",
-            fmis_id: 100965,
-        },
-        WeatherStation {
-            id: "tulliniemi",
-            display_name: "Hanko Tulliniemi",
-            fmis_id: 100946,
-        },
-        WeatherStation {
-            id: "russaro",
-            display_name: "Hanko Russarö",
-            fmis_id: 100932,
-        },
-        WeatherStation {
-            id: "vano",
-            display_name: "Kemiönsaari Vänö",
-            fmis_id: 100945,
-        },
-        WeatherStation {
-            id: "uto",
-            display_name: "Parainen Utö",
-            fmis_id: 100908,
-        },
-        WeatherStation {
-            id: "bogskar",
-            display_name: "Kökar Bogskär",
-            fmis_id: 100921,
-        },
-        WeatherStation {
-            id: "fagerholm",
-            display_name: "Parainen Fagerholm",
-            fmis_id: 100924,
-        },
-        WeatherStation {
-            id: "kirkonkyla",
-            display_name: "Kumlinge kirkonkylä",
-            fmis_id: 100928,
-        },
-        WeatherStation {
-            id: "langnas_satama",
-            display_name: "Lumparland Långnäs satama",
-            fmis_id: 151048,
-        },
-        WeatherStation {
-            id: "lansi_satama",
-            display_name: "Maarianhamina Länsisatama",
-            fmis_id: 151029,
-        },
-        WeatherStation {
-            id: "lotsberget",
-            display_name: "Maarianhamina Lotsberget",
-            fmis_id: 107383,
-        },
-        WeatherStation {
-            id: "nyhamn",
-            display_name: "Lemland Nyhamn",
-            fmis_id: 100909,
-        },
-        WeatherStation {
-            id: "market",
-            display_name: "Hammarland Märket",
-            fmis_id: 100919,
-        },
-        WeatherStation {
-            id: "isokari",
-            display_name: "Kustavi Isokari",
-            fmis_id: 101059,
-        },
-        WeatherStation {
-            id: "kylmapihlaja",
-            display_name: "Rauma Kylmäpihlaja",
-            fmis_id: 101061,
-        },
-        WeatherStation {
-            id: "tahkoluoto_satama",
-            display_name: "Pori Tahkoluoto satama",
-            fmis_id: 101267,
-        },
-        WeatherStation {
-            id: "kristiinankaupunki_majakka",
-            display_name: "Kristiinankaupunki Majakka",
-            fmis_id: 101268,
-        },
-        WeatherStation {
-            id: "salgrund",
-            display_name: "Kaskinen Sälgrund",
-            fmis_id: 101256,
-        },
-        WeatherStation {
-            id: "bredskaret",
-            display_name: "Korsnäs Bredskäret",
-            fmis_id: 101479,
-        },
-        WeatherStation {
-            id: "strommingsbadan",
-            display_name: "Maalahti Strömmingsbådan",
-            fmis_id: 101481,
-        },
-        WeatherStation {
-            id: "valassaaret",
-            display_name: "Mustalahti Valassaaret",
-            fmis_id: 101464,
-        },
-        WeatherStation {
-            id: "kallan",
-            display_name: "Pietarsaari Kallan",
-            fmis_id: 101660,
-        },
-        WeatherStation {
-            id: "tankar",
-            display_name: "Kokkola Tankar",
-            fmis_id: 101661,
-        },
-        WeatherStation {
-            id: "ulkokalla",
-            display_name: "Kalajoki Ulkokalla",
-            fmis_id: 101673,
-        },
-        WeatherStation {
-            id: "nahkiainen",
-            display_name: "Raahe Nahkiainen",
-            fmis_id: 101775,
-        },
-        WeatherStation {
-            id: "lapaluoto_satama",
-            display_name: "Raahe Lapaluoto satama",
-            fmis_id: 101785,
-        },
-        WeatherStation {
-            id: "vihresaari_satama",
-            display_name: "Oulu Vihreäsaari satama",
-            fmis_id: 101794,
-        },
-        WeatherStation {
-            id: "marjaniemi",
-            display_name: "Hailuoto Marjaniemi",
-            fmis_id: 101784,
-        },
-        WeatherStation {
-            id: "kemi_majakka",
-            display_name: "Kemi I Majakka",
-            fmis_id: 101783,
-        },
-        WeatherStation {
-            id: "ajos",
-            display_name: "Kemi Ajos",
-            fmis_id: 101846,
-        },
-        WeatherStation {
-            id: "pulkkilanharju",
-            display_name: "Asikkala Pulkkilanharju",
-            fmis_id: 101185,
-        },
-        WeatherStation {
-            id: "judinsalo",
-            display_name: "Luhanka Judinsalo",
-            fmis_id: 101362,
-        },
-        WeatherStation {
-            id: "hiekkapakka",
-            display_name: "Lappeenranta Hiekkapakka",
-            fmis_id: 101252,
-        },
-        WeatherStation {
-            id: "rukkasluoto",
-            display_name: "Rantasalmi Rukkasluoto",
-            fmis_id: 101436,
-        },
-        WeatherStation {
-            id: "tuiskavanluoto",
-            display_name: "Liperi Tuiskavanluoto",
-            fmis_id: 101628,
-        },
-        WeatherStation {
-            id: "ritoniemi",
-            display_name: "Kuopio Ritoniemi",
-            fmis_id: 101580,
-        },
-        WeatherStation {
-            id: "seitalaassa",
-            display_name: "Inari Seitalaassa",
-            fmis_id: 129963,
-        },
-    ];
-    pub fn create_station_hashmap() -> HashMap<String, WeatherLocation> {
-        let urls = {
-            fn create_entry(name: &str, id: i32) -> WeatherLocation {
-                WeatherLocation {
-                    display_name: name.to_string(),
-                    url: create_weather_url(id),
-                }
+/// A wave buoy or mareograph whose measurements are shown for a weather
+/// station.
+#[derive(Clone)]
+pub struct MarineLocation {
+    pub display_name: String,
+    pub url: String,
+    /// Only mareographs have a sea level forecast
+    pub forecast_url: Option<String>,
+}
+
+struct WeatherStation {
+    id: &'static str,
+    display_name: &'static str,
+    fmis_id: i32,
+    latitude: f64,
+    longitude: f64,
+}
+const WEATHER_STATIONS: [WeatherStation; 50] = [
+    WeatherStation {
+        id: "rajakari",
+        display_name: "Turku Rajakari",
+        fmis_id: 100947,
+        latitude: 60.37750,
+        longitude: 22.09630,
+    },
+    WeatherStation {
+        id: "haapasaari",
+        display_name: "Kotka Haapasaari",
+        fmis_id: 101042,
+        latitude: 60.28730,
+        longitude: 27.18440,
+    },
+    WeatherStation {
+        id: "rankki",
+        display_name: "Kotka Rankki",
+        fmis_id: 101030,
+        latitude: 60.37520,
+        longitude: 26.96220,
+    },
+    WeatherStation {
+        id: "orrengrund",
+        display_name: "Loviisa Orrengrund",
+        fmis_id: 101039,
+        latitude: 60.27450,
+        longitude: 26.44750,
+    },
+    WeatherStation {
+        id: "kilpilahti_satama",
+        display_name: "Porvoo Kilpilahti satama",
+        fmis_id: 100683,
+        latitude: 60.30380,
+        longitude: 25.54920,
+    },
+    WeatherStation {
+        id: "emasalo",
+        display_name: "Porvoo Emäsalo",
+        fmis_id: 101023,
+        latitude: 60.20380,
+        longitude: 25.62530,
+    },
+    WeatherStation {
+        id: "kalbadagrund",
+        display_name: "Porvoo Kalbådagrund",
+        fmis_id: 101022,
+        latitude: 59.98580,
+        longitude: 25.59890,
+    },
+    WeatherStation {
+        id: "vuosaari_satama",
+        display_name: "Helsinki Vuosaari satama",
+        fmis_id: 151028,
+        latitude: 60.20870,
+        longitude: 25.19560,
+    },
+    WeatherStation {
+        id: "itatoukki",
+        display_name: "Sipoo Itätoukki",
+        fmis_id: 105392,
+        latitude: 60.10370,
+        longitude: 25.18580,
+    },
+    WeatherStation {
+        id: "harmaja",
+        display_name: "Helsinki Harmaja",
+        fmis_id: 100996,
+        latitude: 60.10520,
+        longitude: 24.97540,
+    },
+    WeatherStation {
+        id: "helsinki_majakka",
+        display_name: "Helsinki Helsingin Majakka",
+        fmis_id: 101003,
+        latitude: 59.94890,
+        longitude: 24.92620,
+    },
+    WeatherStation {
+        id: "makiluoto",
+        display_name: "Kirkkonummi Mäkiluoto",
+        fmis_id: 100997,
+        latitude: 59.91950,
+        longitude: 24.35040,
+    },
+    WeatherStation {
+        id: "bogaskar",
+        display_name: "Inkoo Bågaskär",
+        fmis_id: 100969,
+        latitude: 59.93110,
+        longitude: 23.97670,
+    },
+    WeatherStation {
+        id: "jussaro",
+        display_name: "Raasepori Jussarö",
+        fmis_id: 100965,
+        latitude: 59.82060,
+        longitude: 23.57190,
+    },
+    WeatherStation {
+        id: "tulliniemi",
+        display_name: "Hanko Tulliniemi",
+        fmis_id: 100946,
+        latitude: 59.80960,
+        longitude: 22.90960,
+    },
+    WeatherStation {
+        id: "russaro",
+        display_name: "Hanko Russarö",
+        fmis_id: 100932,
+        latitude: 59.77320,
+        longitude: 22.94870,
+    },
+    WeatherStation {
+        id: "vano",
+        display_name: "Kemiönsaari Vänö",
+        fmis_id: 100945,
+        latitude: 59.86780,
+        longitude: 22.19190,
+    },
+    WeatherStation {
+        id: "uto",
+        display_name: "Parainen Utö",
+        fmis_id: 100908,
+        latitude: 59.77910,
+        longitude: 21.37480,
+    },
+    WeatherStation {
+        id: "bogskar",
+        display_name: "Kökar Bogskär",
+        fmis_id: 100921,
+        latitude: 59.50310,
+        longitude: 20.35030,
+    },
+    WeatherStation {
+        id: "fagerholm",
+        display_name: "Parainen Fagerholm",
+        fmis_id: 100924,
+        latitude: 60.10990,
+        longitude: 21.69870,
+    },
+    WeatherStation {
+        id: "kirkonkyla",
+        display_name: "Kumlinge kirkonkylä",
+        fmis_id: 100928,
+        latitude: 60.25910,
+        longitude: 20.75050,
+    },
+    WeatherStation {
+        id: "langnas_satama",
+        display_name: "Lumparland Långnäs satama",
+        fmis_id: 151048,
+        latitude: 60.11670,
+        longitude: 20.29860,
+    },
+    WeatherStation {
+        id: "lansi_satama",
+        display_name: "Maarianhamina Länsisatama",
+        fmis_id: 151029,
+        latitude: 60.09580,
+        longitude: 19.92920,
+    },
+    WeatherStation {
+        id: "lotsberget",
+        display_name: "Maarianhamina Lotsberget",
+        fmis_id: 107383,
+        latitude: 60.09480,
+        longitude: 19.93920,
+    },
+    WeatherStation {
+        id: "nyhamn",
+        display_name: "Lemland Nyhamn",
+        fmis_id: 100909,
+        latitude: 59.96140,
+        longitude: 19.95360,
+    },
+    WeatherStation {
+        id: "market",
+        display_name: "Hammarland Märket",
+        fmis_id: 100919,
+        latitude: 60.30070,
+        longitude: 19.13060,
+    },
+    WeatherStation {
+        id: "isokari",
+        display_name: "Kustavi Isokari",
+        fmis_id: 101059,
+        latitude: 60.71880,
+        longitude: 21.02700,
+    },
+    WeatherStation {
+        id: "kylmapihlaja",
+        display_name: "Rauma Kylmäpihlaja",
+        fmis_id: 101061,
+        latitude: 61.14490,
+        longitude: 21.30360,
+    },
+    WeatherStation {
+        id: "tahkoluoto_satama",
+        display_name: "Pori Tahkoluoto satama",
+        fmis_id: 101267,
+        latitude: 61.62700,
+        longitude: 21.38340,
+    },
+    WeatherStation {
+        id: "kristiinankaupunki_majakka",
+        display_name: "Kristiinankaupunki Majakka",
+        fmis_id: 101268,
+        latitude: 62.20340,
+        longitude: 21.17380,
+    },
+    WeatherStation {
+        id: "salgrund",
+        display_name: "Kaskinen Sälgrund",
+        fmis_id: 101256,
+        latitude: 62.33500,
+        longitude: 21.19340,
+    },
+    WeatherStation {
+        id: "bredskaret",
+        display_name: "Korsnäs Bredskäret",
+        fmis_id: 101479,
+        latitude: 62.95560,
+        longitude: 21.18390,
+    },
+    WeatherStation {
+        id: "strommingsbadan",
+        display_name: "Maalahti Strömmingsbådan",
+        fmis_id: 101481,
+        latitude: 62.97780,
+        longitude: 20.74330,
+    },
+    WeatherStation {
+        id: "valassaaret",
+        display_name: "Mustalahti Valassaaret",
+        fmis_id: 101464,
+        latitude: 63.43500,
+        longitude: 21.06610,
+    },
+    WeatherStation {
+        id: "kallan",
+        display_name: "Pietarsaari Kallan",
+        fmis_id: 101660,
+        latitude: 63.74660,
+        longitude: 22.52540,
+    },
+    WeatherStation {
+        id: "tankar",
+        display_name: "Kokkola Tankar",
+        fmis_id: 101661,
+        latitude: 63.95170,
+        longitude: 22.84850,
+    },
+    WeatherStation {
+        id: "ulkokalla",
+        display_name: "Kalajoki Ulkokalla",
+        fmis_id: 101673,
+        latitude: 64.33030,
+        longitude: 23.44920,
+    },
+    WeatherStation {
+        id: "nahkiainen",
+        display_name: "Raahe Nahkiainen",
+        fmis_id: 101775,
+        latitude: 64.60340,
+        longitude: 23.90370,
+    },
+    WeatherStation {
+        id: "lapaluoto_satama",
+        display_name: "Raahe Lapaluoto satama",
+        fmis_id: 101785,
+        latitude: 64.66840,
+        longitude: 24.41010,
+    },
+    WeatherStation {
+        id: "vihresaari_satama",
+        display_name: "Oulu Vihreäsaari satama",
+        fmis_id: 101794,
+        latitude: 65.00690,
+        longitude: 25.39260,
+    },
+    WeatherStation {
+        id: "marjaniemi",
+        display_name: "Hailuoto Marjaniemi",
+        fmis_id: 101784,
+        latitude: 65.03990,
+        longitude: 24.56160,
+    },
+    WeatherStation {
+        id: "kemi_majakka",
+        display_name: "Kemi I Majakka",
+        fmis_id: 101783,
+        latitude: 65.39290,
+        longitude: 24.09930,
+    },
+    WeatherStation {
+        id: "ajos",
+        display_name: "Kemi Ajos",
+        fmis_id: 101846,
+        latitude: 65.67300,
+        longitude: 24.51540,
+    },
+    WeatherStation {
+        id: "pulkkilanharju",
+        display_name: "Asikkala Pulkkilanharju",
+        fmis_id: 101185,
+        latitude: 61.26910,
+        longitude: 25.69260,
+    },
+    WeatherStation {
+        id: "judinsalo",
+        display_name: "Luhanka Judinsalo",
+        fmis_id: 101362,
+        latitude: 61.71140,
+        longitude: 25.50810,
+    },
+    WeatherStation {
+        id: "hiekkapakka",
+        display_name: "Lappeenranta Hiekkapakka",
+        fmis_id: 101252,
+        latitude: 61.20280,
+        longitude: 28.47270,
+    },
+    WeatherStation {
+        id: "rukkasluoto",
+        display_name: "Rantasalmi Rukkasluoto",
+        fmis_id: 101436,
+        latitude: 62.06060,
+        longitude: 28.56960,
+    },
+    WeatherStation {
+        id: "tuiskavanluoto",
+        display_name: "Liperi Tuiskavanluoto",
+        fmis_id: 101628,
+        latitude: 62.54830,
+        longitude: 29.66560,
+    },
+    WeatherStation {
+        id: "ritoniemi",
+        display_name: "Kuopio Ritoniemi",
+        fmis_id: 101580,
+        latitude: 62.79880,
+        longitude: 27.90230,
+    },
+    WeatherStation {
+        id: "seitalaassa",
+        display_name: "Inari Seitalaassa",
+        fmis_id: 129963,
+        latitude: 69.04670,
+        longitude: 27.76320,
+    },
+];
+
+/// Stations on lakes rather than at sea
+const INLAND_STATIONS: [&str; 7] = [
+    "pulkkilanharju",
+    "judinsalo",
+    "hiekkapakka",
+    "rukkasluoto",
+    "tuiskavanluoto",
+    "ritoniemi",
+    "seitalaassa",
+];
+
+struct MarineStation {
+    display_name: &'static str,
+    fmis_id: i32,
+    latitude: f64,
+    longitude: f64,
+    /// Ids of the weather stations whose conditions this station represents
+    nearby_stations: &'static [&'static str],
+}
+const WAVE_BUOYS: [MarineStation; 3] = [
+    MarineStation {
+        display_name: "Helsinki Suomenlinna aaltopoiju",
+        fmis_id: 134254,
+        latitude: 60.12333,
+        longitude: 24.97250,
+        nearby_stations: &[
+            "kilpilahti_satama",
+            "emasalo",
+            "kalbadagrund",
+            "vuosaari_satama",
+            "itatoukki",
+            "harmaja",
+            "helsinki_majakka",
+            "makiluoto",
+        ],
+    },
+    MarineStation {
+        display_name: "Pohjois-Itämeri aaltopoiju",
+        fmis_id: 134220,
+        latitude: 59.24833,
+        longitude: 21.00000,
+        nearby_stations: &[
+            "bogaskar",
+            "jussaro",
+            "tulliniemi",
+            "russaro",
+            "vano",
+            "uto",
+            "bogskar",
+            "fagerholm",
+            "kirkonkyla",
+            "langnas_satama",
+            "lansi_satama",
+            "lotsberget",
+            "nyhamn",
+            "market",
+        ],
+    },
+    MarineStation {
+        display_name: "Selkämeri aaltopoiju",
+        fmis_id: 134048,
+        latitude: 61.80000,
+        longitude: 20.23333,
+        nearby_stations: &[
+            "isokari",
+            "kylmapihlaja",
+            "tahkoluoto_satama",
+            "kristiinankaupunki_majakka",
+            "salgrund",
+        ],
+    },
+];
+
+const MAREOGRAPHS: [MarineStation; 12] = [
+    MarineStation {
+        display_name: "Helsinki Kaivopuisto mareografi",
+        fmis_id: 132310,
+        latitude: 60.15363,
+        longitude: 24.95622,
+        nearby_stations: &[
+            "kilpilahti_satama",
+            "emasalo",
+            "kalbadagrund",
+            "vuosaari_satama",
+            "itatoukki",
+            "harmaja",
+            "helsinki_majakka",
+            "makiluoto",
+        ],
+    },
+    MarineStation {
+        display_name: "Hamina Pitäjänsaari mareografi",
+        fmis_id: 134340,
+        latitude: 60.56277,
+        longitude: 27.17919,
+        nearby_stations: &["haapasaari", "rankki", "orrengrund"],
+    },
+    MarineStation {
+        display_name: "Hanko Pikku Kolalahti mareografi",
+        fmis_id: 134253,
+        latitude: 59.82287,
+        longitude: 22.97658,
+        nearby_stations: &["bogaskar", "jussaro", "tulliniemi", "russaro"],
+    },
+    MarineStation {
+        display_name: "Turku Ruissalo Saaronniemi mareografi",
+        fmis_id: 134225,
+        latitude: 60.42808,
+        longitude: 22.10049,
+        nearby_stations: &["rajakari", "vano", "uto", "fagerholm"],
+    },
+    MarineStation {
+        display_name: "Föglö Degerby mareografi",
+        fmis_id: 134251,
+        latitude: 60.03188,
+        longitude: 20.38482,
+        nearby_stations: &[
+            "bogskar",
+            "kirkonkyla",
+            "langnas_satama",
+            "lansi_satama",
+            "lotsberget",
+            "nyhamn",
+            "market",
+        ],
+    },
+    MarineStation {
+        display_name: "Rauma Petäjäs mareografi",
+        fmis_id: 134224,
+        latitude: 61.13353,
+        longitude: 21.42582,
+        nearby_stations: &["isokari", "kylmapihlaja", "tahkoluoto_satama"],
+    },
+    MarineStation {
+        display_name: "Kaskinen Ådskär mareografi",
+        fmis_id: 134252,
+        latitude: 62.34395,
+        longitude: 21.21483,
+        nearby_stations: &["kristiinankaupunki_majakka", "salgrund"],
+    },
+    MarineStation {
+        display_name: "Vaasa Vaskiluoto mareografi",
+        fmis_id: 134223,
+        latitude: 63.08151,
+        longitude: 21.57107,
+        nearby_stations: &["bredskaret", "strommingsbadan", "valassaaret"],
+    },
+    MarineStation {
+        display_name: "Pietarsaari Leppäluoto mareografi",
+        fmis_id: 134250,
+        latitude: 63.70857,
+        longitude: 22.68958,
+        nearby_stations: &["kallan", "tankar"],
+    },
+    MarineStation {
+        display_name: "Raahe Lapaluoto mareografi",
+        fmis_id: 100540,
+        latitude: 64.66630,
+        longitude: 24.40708,
+        nearby_stations: &["ulkokalla", "nahkiainen", "lapaluoto_satama"],
+    },
+    MarineStation {
+        display_name: "Oulu Toppila mareografi",
+        fmis_id: 134248,
+        latitude: 65.04030,
+        longitude: 25.41820,
+        nearby_stations: &["vihresaari_satama", "marjaniemi"],
+    },
+    MarineStation {
+        display_name: "Kemi Ajos mareografi",
+        fmis_id: 100539,
+        latitude: 65.67337,
+        longitude: 24.51526,
+        nearby_stations: &["kemi_majakka", "ajos"],
+    },
+];
+
+fn find_nearby<'a>(
+    marine_stations: &'a [MarineStation],
+    station_id: &str,
+) -> Option<&'a MarineStation> {
+    marine_stations
+        .iter()
+        .find(|marine_station| marine_station.nearby_stations.contains(&station_id))
+}
+
+/// Coordinates of the station catalogue are preferred over the ones in
+/// our lists.
+fn coordinates(
+    catalogue: &HashMap<i32, CatalogueStation>,
+    fmis_id: i32,
+    latitude: f64,
+    longitude: f64,
+) -> (f64, f64) {
+    catalogue
+        .get(&fmis_id)
+        .map_or((latitude, longitude), |station| {
+            (station.latitude, station.longitude)
+        })
+}
+
+pub fn create_station_hashmap(
+    format: ResponseFormat,
+    config: &Config,
+    catalogue: &HashMap<i32, CatalogueStation>,
+) -> HashMap<String, WeatherLocation> {
+    let base_url = config.fmi_base_url.as_str();
+    let urls = {
+        let create_entry = |station: &WeatherStation| {
+            let (latitude, longitude) = coordinates(
+                catalogue,
+                station.fmis_id,
+                station.latitude,
+                station.longitude,
+            );
+            WeatherLocation {
+                display_name: station.display_name.to_string(),
+                url: create_weather_url(base_url, station.fmis_id, format),
+                forecast_url: create_forecast_url(base_url, station.fmis_id, format),
+                wave_forecast_url: (!INLAND_STATIONS.contains(&station.id))
+                    .then(|| create_wave_forecast_url(base_url, latitude, longitude, format)),
+                lightning_url: create_lightning_url(
+                    base_url,
+                    latitude,
+                    longitude,
+                    config.lightning_radius_km,
+                    format,
+                ),
+                daily_url: create_daily_url(base_url, station.fmis_id),
+                hourly_url: create_hourly_url(base_url, station.fmis_id),
+                format,
+                fmis_id: station.fmis_id,
+                latitude,
+                longitude,
+                wave_buoy: find_nearby(&WAVE_BUOYS, station.id).map(|buoy| MarineLocation {
+                    display_name: buoy.display_name.to_string(),
+                    url: create_wave_url(base_url, buoy.fmis_id, format),
+                    forecast_url: None,
+                }),
+                mareograph: find_nearby(&MAREOGRAPHS, station.id).map(|mareograph| {
+                    let (latitude, longitude) = coordinates(
+                        catalogue,
+                        mareograph.fmis_id,
+                        mareograph.latitude,
+                        mareograph.longitude,
+                    );
+                    MarineLocation {
+                        display_name: mareograph.display_name.to_string(),
+                        url: create_mareograph_url(base_url, mareograph.fmis_id, format),
+                        forecast_url: Some(create_sea_level_forecast_url(
+                            base_url, latitude, longitude, format,
+                        )),
+                    }
+                }),
+                catalogue: catalogue.get(&station.fmis_id).cloned(),
             }
-            WEATHER_STATIONS
-                .iter()
-                .map(
-                    |WeatherStation {
-                         display_name,
-                         fmis_id,
-                         id,
-                     }| (id.to_string(), create_entry(display_name, *fmis_id)),
-                )
-                .collect()
         };
-        urls
-    }
-    fn create_weather_url(station_id: i32) -> String {
-        format!("https://opendata.fmi.fi/wfs?service=WFS&version=2.0.0&request=getFeature&parameters=Temperature,WindDirection,WindSpeedMS,WindGust&storedquery_id=fmi::observations::weather::simple&fmisid={}", station_id)
+        WEATHER_STATIONS
+            .iter()
+            .map(|station| (station.id.to_string(), create_entry(station)))
+            .collect()
+    };
+    urls
+}
+/// Describes the stations of our lists that the catalogue does not have
+/// or lists as no longer active.
+pub fn stations_missing_from_catalogue(catalogue: &HashMap<i32, CatalogueStation>) -> Vec<String> {
+    let weather_stations = WEATHER_STATIONS
+        .iter()
+        .map(|station| (station.display_name, station.fmis_id));
+    let marine_stations = WAVE_BUOYS
+        .iter()
+        .chain(MAREOGRAPHS.iter())
+        .map(|station| (station.display_name, station.fmis_id));
+    weather_stations
+        .chain(marine_stations)
+        .filter_map(|(display_name, fmis_id)| match catalogue.get(&fmis_id) {
+            None => Some(format!(
+                "{} ({}) is not in the catalogue",
+                display_name, fmis_id
+            )),
+            Some(station) if !station.active => Some(format!(
+                "{} ({}) is no longer active",
+                display_name, fmis_id
+            )),
+            Some(_) => None,
+        })
+        .collect()
+}
+
+const OBSERVATION_PARAMETERS: &str = "Temperature,WindDirection,WindSpeedMS,WindGust,Pressure,Humidity,DewPoint,Visibility,TotalCloudCover,Precipitation1h";
+
+/// A `multipointcoverage` query for several stations at once, so the
+/// response can be split by station.
+pub fn create_batch_weather_url(base_url: &str, fmis_ids: &[i32]) -> String {
+    let fmis_id_parameters: String = fmis_ids
+        .iter()
+        .map(|fmis_id| format!("&fmisid={}", fmis_id))
+        .collect();
+    format!("{}?service=WFS&version=2.0.0&request=getFeature&parameters={}&storedquery_id=fmi::observations::weather::multipointcoverage{}", base_url, OBSERVATION_PARAMETERS, fmis_id_parameters)
+}
+fn create_weather_url(base_url: &str, station_id: i32, format: ResponseFormat) -> String {
+    format!("{}?service=WFS&version=2.0.0&request=getFeature&parameters={}&storedquery_id=fmi::observations::weather::{}&fmisid={}", base_url, OBSERVATION_PARAMETERS, format.stored_query_suffix(), station_id)
+}
+fn create_daily_url(base_url: &str, station_id: i32) -> String {
+    format!("{}?service=WFS&version=2.0.0&request=getFeature&parameters=tmin,tmax&storedquery_id=fmi::observations::weather::daily::simple&fmisid={}", base_url, station_id)
+}
+fn create_hourly_url(base_url: &str, station_id: i32) -> String {
+    format!("{}?service=WFS&version=2.0.0&request=getFeature&parameters=WS_PT1H_AVG,WG_PT1H_MAX&storedquery_id=fmi::observations::weather::hourly::simple&fmisid={}", base_url, station_id)
+}
+fn create_forecast_url(base_url: &str, station_id: i32, format: ResponseFormat) -> String {
+    format!("{}?service=WFS&version=2.0.0&request=getFeature&parameters=Temperature,WindDirection,WindSpeedMS,WindGust&storedquery_id=fmi::forecast::harmonie::surface::point::{}&fmisid={}", base_url, format.stored_query_suffix(), station_id)
+}
+fn create_mareograph_url(base_url: &str, station_id: i32, format: ResponseFormat) -> String {
+    format!("{}?service=WFS&version=2.0.0&request=getFeature&parameters=WLEVN2K_PT1S_AVG,WLEV_PT1S_AVG&storedquery_id=fmi::observations::mareograph::{}&fmisid={}", base_url, format.stored_query_suffix(), station_id)
+}
+fn create_sea_level_forecast_url(
+    base_url: &str,
+    latitude: f64,
+    longitude: f64,
+    format: ResponseFormat,
+) -> String {
+    format!("{}?service=WFS&version=2.0.0&request=getFeature&parameters=SeaLevel,SeaLevelN2000&storedquery_id=fmi::forecast::oaas::sealevel::point::{}&latlon={},{}", base_url, format.stored_query_suffix(), latitude, longitude)
+}
+fn create_wave_forecast_url(
+    base_url: &str,
+    latitude: f64,
+    longitude: f64,
+    format: ResponseFormat,
+) -> String {
+    format!("{}?service=WFS&version=2.0.0&request=getFeature&parameters=SigWaveHeight,WaveDirection,WavePeriod&storedquery_id=fmi::forecast::wam::point::{}&latlon={},{}", base_url, format.stored_query_suffix(), latitude, longitude)
+}
+fn create_lightning_url(
+    base_url: &str,
+    latitude: f64,
+    longitude: f64,
+    radius_kilometers: f64,
+    format: ResponseFormat,
+) -> String {
+    create_area_lightning_url(
+        base_url,
+        &[(latitude, longitude)],
+        radius_kilometers,
+        format,
+    )
+}
+/// Covers a box around all of `points`, given as latitude and longitude, so
+/// one query holds the strikes near every one of them.
+pub fn create_area_lightning_url(
+    base_url: &str,
+    points: &[(f64, f64)],
+    radius_kilometers: f64,
+    format: ResponseFormat,
+) -> String {
+    const KILOMETERS_PER_DEGREE_OF_LATITUDE: f64 = 111.0;
+    let latitude_offset = radius_kilometers / KILOMETERS_PER_DEGREE_OF_LATITUDE;
+    let (mut west, mut south, mut east, mut north) = (180.0f64, 90.0f64, -180.0f64, -90.0f64);
+    for (latitude, longitude) in points {
+        let longitude_offset = latitude_offset / latitude.to_radians().cos();
+        west = west.min(longitude - longitude_offset);
+        south = south.min(latitude - latitude_offset);
+        east = east.max(longitude + longitude_offset);
+        north = north.max(latitude + latitude_offset);
     }
+    format!("{}?service=WFS&version=2.0.0&request=getFeature&parameters=peak_current&storedquery_id=fmi::observations::lightning::{}&bbox={:.4},{:.4},{:.4},{:.4}", base_url, format.stored_query_suffix(), west, south, east, north)
+}
+fn create_wave_url(base_url: &str, station_id: i32, format: ResponseFormat) -> String {
+    format!("{}?service=WFS&version=2.0.0&request=getFeature&parameters=WaveHs,WTP,ModalWDi,TWATER&storedquery_id=fmi::observations::wave::{}&fmisid={}", base_url, format.stored_query_suffix(), station_id)
 }
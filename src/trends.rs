@@ -0,0 +1,218 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::model::{Observation, ObservationSeries, ParameterType};
+
+/// How far back the series used for the trends reaches.
+pub const TREND_HOURS: i64 = 3;
+
+/// An observation this far from the start of a trend's window still counts
+/// as the start value.
+const START_TOLERANCE_MINUTES: i64 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Tendency {
+    Rising,
+    Steady,
+    Falling,
+}
+
+/// The change of a value over a window ending at the newest observation.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trend {
+    pub change: f64,
+    pub hours: i64,
+    pub tendency: Tendency,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Shift {
+    /// Turning clockwise
+    Veering,
+    Steady,
+    /// Turning counterclockwise
+    Backing,
+}
+
+/// The turn of the wind direction over a window, positive clockwise.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectionTrend {
+    pub change_degrees: f64,
+    pub hours: i64,
+    pub shift: Shift,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trends {
+    /// Over the last hour
+    pub wind_speed: Option<Trend>,
+    /// Over the last hour
+    pub wind_gust_speed: Option<Trend>,
+    /// Over the last two hours
+    pub wind_direction: Option<DirectionTrend>,
+    /// Over the last three hours, the pressure tendency of synoptic reports
+    pub pressure: Option<Trend>,
+}
+
+/// Computes the trends from a series reaching at least `TREND_HOURS` back.
+pub fn compute_trends(series: &ObservationSeries) -> Trends {
+    Trends {
+        wind_speed: trend(series.get(ParameterType::WindSpeed), 1, 1.0),
+        wind_gust_speed: trend(series.get(ParameterType::WindGustSpeed), 1, 2.0),
+        wind_direction: direction_trend(series.get(ParameterType::WindDirection), 2, 20.0),
+        pressure: trend(series.get(ParameterType::Pressure), TREND_HOURS, 1.0),
+    }
+}
+
+/// The newest value and the value closest to `hours` before it.
+fn endpoints(observations: &[Observation], hours: i64) -> Option<(f64, f64)> {
+    let (end_time, end_value) = observations
+        .iter()
+        .rev()
+        .find_map(|observation| Some((observation.time, observation.value?)))?;
+    let start_time = end_time - Duration::hours(hours);
+    let start_value = observations
+        .iter()
+        .filter_map(|observation| {
+            Some((distance(observation.time, start_time), observation.value?))
+        })
+        .filter(|(distance, _)| *distance <= Duration::minutes(START_TOLERANCE_MINUTES))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, value)| value)?;
+    Some((start_value, end_value))
+}
+
+fn distance(a: DateTime<Utc>, b: DateTime<Utc>) -> Duration {
+    (a - b).abs()
+}
+
+/// Changes smaller than `threshold` count as steady.
+fn trend(observations: &[Observation], hours: i64, threshold: f64) -> Option<Trend> {
+    let (start, end) = endpoints(observations, hours)?;
+    let change = end - start;
+    let tendency = if change >= threshold {
+        Tendency::Rising
+    } else if change <= -threshold {
+        Tendency::Falling
+    } else {
+        Tendency::Steady
+    };
+    Some(Trend {
+        change,
+        hours,
+        tendency,
+    })
+}
+
+/// Turns smaller than `threshold_degrees` count as steady.
+fn direction_trend(
+    observations: &[Observation],
+    hours: i64,
+    threshold_degrees: f64,
+) -> Option<DirectionTrend> {
+    let (start, end) = endpoints(observations, hours)?;
+    // The shorter way around, e.g. 350° to 10° is 20° clockwise
+    let change_degrees = (end - start + 540.0).rem_euclid(360.0) - 180.0;
+    let shift = if change_degrees >= threshold_degrees {
+        Shift::Veering
+    } else if change_degrees <= -threshold_degrees {
+        Shift::Backing
+    } else {
+        Shift::Steady
+    };
+    Some(DirectionTrend {
+        change_degrees,
+        hours,
+        shift,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A series of one parameter with a value every 10 minutes over the
+    /// last `TREND_HOURS`, from `start` to `end` in even steps.
+    fn series(parameter: ParameterType, start: f64, end: f64) -> ObservationSeries {
+        let end_time = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let steps = TREND_HOURS * 6;
+        let observations = (0..=steps)
+            .map(|step| Observation {
+                time: end_time - Duration::minutes(10 * (steps - step)),
+                value: Some(start + (end - start) * step as f64 / steps as f64),
+            })
+            .collect();
+        ObservationSeries {
+            parameters: [(parameter, observations)].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn pressure_rising_falling_and_steady() {
+        let rising = compute_trends(&series(ParameterType::Pressure, 1000.0, 1003.0));
+        let rising = rising.pressure.unwrap();
+        assert_eq!(rising.tendency, Tendency::Rising);
+        assert_eq!(rising.hours, TREND_HOURS);
+        assert!((rising.change - 3.0).abs() < 1e-9);
+
+        let falling = compute_trends(&series(ParameterType::Pressure, 1003.0, 1000.0));
+        assert_eq!(falling.pressure.unwrap().tendency, Tendency::Falling);
+
+        let steady = compute_trends(&series(ParameterType::Pressure, 1000.0, 1000.5));
+        assert_eq!(steady.pressure.unwrap().tendency, Tendency::Steady);
+    }
+
+    #[test]
+    fn wind_speed_compares_the_last_hour() {
+        // 0 to 6 m/s over three hours is 2 m/s over the last hour
+        let trends = compute_trends(&series(ParameterType::WindSpeed, 0.0, 6.0));
+        let wind_speed = trends.wind_speed.unwrap();
+        assert_eq!(wind_speed.hours, 1);
+        assert!((wind_speed.change - 2.0).abs() < 1e-9);
+        assert_eq!(wind_speed.tendency, Tendency::Rising);
+    }
+
+    /// Wind from `start` degrees two hours ago and from `end` now.
+    fn direction_series(start: f64, end: f64) -> ObservationSeries {
+        let mut series = series(ParameterType::WindDirection, start, start);
+        let observations = series
+            .parameters
+            .get_mut(&ParameterType::WindDirection)
+            .unwrap();
+        if let Some(newest) = observations.last_mut() {
+            newest.value = Some(end);
+        }
+        series
+    }
+
+    #[test]
+    fn direction_turns_the_shorter_way_around_north() {
+        let veering = compute_trends(&direction_series(350.0, 10.0));
+        let veering = veering.wind_direction.unwrap();
+        assert!((veering.change_degrees - 20.0).abs() < 1e-9);
+        assert_eq!(veering.shift, Shift::Veering);
+
+        let backing = compute_trends(&direction_series(10.0, 350.0));
+        let backing = backing.wind_direction.unwrap();
+        assert!((backing.change_degrees + 20.0).abs() < 1e-9);
+        assert_eq!(backing.shift, Shift::Backing);
+    }
+
+    #[test]
+    fn missing_start_value_gives_no_trend() {
+        let mut series = series(ParameterType::Pressure, 1000.0, 1003.0);
+        let observations = series.parameters.get_mut(&ParameterType::Pressure).unwrap();
+        // Nothing within the tolerance of three hours before the newest value
+        for observation in observations.iter_mut().take(3) {
+            observation.value = None;
+        }
+        assert!(compute_trends(&series).pressure.is_none());
+        assert!(compute_trends(&series).wind_speed.is_none());
+    }
+}
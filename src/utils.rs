@@ -1 +1,2 @@
+pub mod distance;
 pub mod map_degrees_to_compass;
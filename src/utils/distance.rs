@@ -0,0 +1,16 @@
+const EARTH_RADIUS_KILOMETERS: f64 = 6371.0;
+
+/// Great-circle distance between two points given as latitude and longitude
+/// in degrees.
+pub fn kilometers(
+    (latitude_a, longitude_a): (f64, f64),
+    (latitude_b, longitude_b): (f64, f64),
+) -> f64 {
+    let delta_latitude = (latitude_b - latitude_a).to_radians();
+    let delta_longitude = (longitude_b - longitude_a).to_radians();
+    let a = (delta_latitude / 2.0).sin().powi(2)
+        + latitude_a.to_radians().cos()
+            * latitude_b.to_radians().cos()
+            * (delta_longitude / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KILOMETERS * a.sqrt().asin()
+}